@@ -1,53 +1,170 @@
-use std::mem;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 
 // mac os 下，环回地址报文开头，不再通过报文判断
 const LOOPBACK_ADDRESS_START: [u8; 4] = [2, 0, 0, 0];
+// 以太网头长度
+const ETHERNET_HEAD_LEN: usize = 14;
+// IPv4最小头长度
+const IPV4_MIN_HEAD_LEN: usize = 20;
+// IPv6固定头长度
+const IPV6_HEAD_LEN: usize = 40;
+// TCP最小头长度
+const TCP_MIN_HEAD_LEN: usize = 20;
+// TCP标志位
+pub const TCP_FIN: u8 = 0x01;
+pub const TCP_SYN: u8 = 0x02;
+pub const TCP_RST: u8 = 0x04;
+pub const TCP_PSH: u8 = 0x08;
+pub const TCP_ACK: u8 = 0x10;
+pub const TCP_URG: u8 = 0x20;
+// UDP头长度
+const UDP_HEAD_LEN: usize = 8;
+// ICMP头长度，类型(1) + 代码(1) + 校验和(2) + 其余部分(4)
+const ICMP_HEAD_LEN: usize = 8;
+// ICMPv6头长度，类型(1) + 代码(1) + 校验和(2)，之后是消息体
+const ICMPV6_HEAD_LEN: usize = 4;
+// Linux cooked capture 头长度
+const LINUX_SLL_HEAD_LEN: usize = 16;
+const LINUX_SLL2_HEAD_LEN: usize = 20;
+// VLAN标签、MPLS标签长度
+const VLAN_TAG_LEN: usize = 4;
+const MPLS_LABEL_LEN: usize = 4;
+// GRE最小头长度，标志和版本(2) + 协议类型(2)
+const GRE_MIN_HEAD_LEN: usize = 4;
+// VXLAN头长度
+const VXLAN_HEAD_LEN: usize = 8;
+// Geneve最小头长度，不含选项
+const GENEVE_MIN_HEAD_LEN: usize = 8;
+// VXLAN、Geneve的UDP端口
+const VXLAN_PORT: u16 = 4789;
+const GENEVE_PORT: u16 = 6081;
+// 隧道最多解析的层数，防止构造的报文无限嵌套
+const TUNNEL_MAX_DEPTH: usize = 4;
 
 // 协议类型
+// 分层解析，每一层都有解析状态，报文不完整或者格式错误时，不会panic
 #[derive(Debug)]
 pub struct ProType {
     link_pro: LinkPro,
+    pub link_state: LayerState,
     pub link_start: usize,
     link_head_len: usize,
+    // 报文方向，只有Linux cooked capture（监听any）时才有
+    pub direction: Option<PacketDirection>,
+    // 网口序号，只有SLL2时才有
+    pub if_index: Option<u32>,
+    // VLAN ID，从外到内，802.1Q、802.1ad(QinQ)
+    pub vlan_ids: Vec<u16>,
+    // MPLS标签，从栈顶到栈底
+    pub mpls_labels: Vec<u32>,
     network_pro: NetworkPro,
+    pub network_state: LayerState,
     pub network_start: usize,
+    // 网络层头长度，IPv6包含扩展头
     network_head_len: usize,
+    // 源IP、目的IP，只有IPv4、IPv6时才有
+    pub src_ip: Option<IpAddr>,
+    pub dst_ip: Option<IpAddr>,
+    // 网络层记录的上层协议号，IPv4的协议字段，IPv6最后一个扩展头的下一个头字段
+    next_pro: u8,
+    // 报文有效数据的结束位置，根据IP报文长度计算，去掉以太网填充
+    pub packet_end: usize,
     pub transport_pro: TransportPro,
+    pub transport_state: LayerState,
     pub transport_start: usize,
     pub transport_head_len: usize,
+    // 源端口、目的端口，只有TCP、UDP时才有
+    pub src_port: u16,
+    pub dst_port: u16,
+    // TCP序列号、标志位，只有TCP时才有
+    pub tcp_seq: u32,
+    pub tcp_flags: u8,
+    // 隧道协议，有值时，应用层是内层报文
+    pub tunnel_pro: Option<TunnelPro>,
+    // 隧道内层报文，位置也是相对于整个报文的
+    pub inner: Option<Box<ProType>>,
     pub application_pro: ApplicationPro,
+    pub application_state: LayerState,
     pub application_start: usize,
 }
 
 impl ProType {
     pub(crate) fn from_with_linktype(linktype: &pcap::Linktype, data: &[u8]) -> Self {
-        if data.is_empty() {
-            return ProType {
-                link_pro: LinkPro::Unsupported,
-                link_start: 0,
-                link_head_len: 0,
-                network_pro: NetworkPro::Unsupported,
-                network_start: 0,
-                network_head_len: 0,
-                transport_pro: TransportPro::Unsupported,
-                transport_start: 0,
-                transport_head_len: 0,
-                application_pro: ApplicationPro::Unsupported,
-                application_start: 0,
-            };
-        }
-        let mut pro_type = mem::MaybeUninit::<ProType>::uninit();
-
-        unsafe {
-            analyze_link_with_linktype(linktype, pro_type.as_mut_ptr(), data);
-            analyze_network(pro_type.as_mut_ptr(), data);
-            analyze_transport(pro_type.as_mut_ptr(), data);
-            analyze_application(pro_type.as_mut_ptr(), data);
-            return pro_type.assume_init();
+        let mut pro_type = ProType::new(data.len());
+        analyze_link_with_linktype(linktype, &mut pro_type, data);
+        analyze_upper(&mut pro_type, data, 0);
+        pro_type
+    }
+
+    // 从外到内，遍历所有隧道层
+    pub fn layers(&self) -> impl Iterator<Item = &ProType> {
+        std::iter::successors(Some(self), |pro_type| pro_type.inner.as_deref())
+    }
+
+    // 最内层报文，没有隧道时是自己
+    pub fn innermost(&self) -> &ProType {
+        self.layers().last().unwrap_or(self)
+    }
+
+    // 源地址，IP + 端口
+    pub fn src_addr(&self) -> Option<SocketAddr> {
+        self.src_ip.map(|ip| SocketAddr::new(ip, self.src_port))
+    }
+
+    // 目的地址，IP + 端口
+    pub fn dst_addr(&self) -> Option<SocketAddr> {
+        self.dst_ip.map(|ip| SocketAddr::new(ip, self.dst_port))
+    }
+
+    // 所有层都不支持，解析从这里开始
+    fn new(data_len: usize) -> Self {
+        ProType {
+            link_pro: LinkPro::Unsupported,
+            link_state: LayerState::Unsupported,
+            link_start: 0,
+            link_head_len: 0,
+            direction: None,
+            if_index: None,
+            vlan_ids: Vec::new(),
+            mpls_labels: Vec::new(),
+            network_pro: NetworkPro::Unsupported,
+            network_state: LayerState::Unsupported,
+            network_start: 0,
+            network_head_len: 0,
+            src_ip: None,
+            dst_ip: None,
+            next_pro: 0,
+            packet_end: data_len,
+            transport_pro: TransportPro::Unsupported,
+            transport_state: LayerState::Unsupported,
+            transport_start: 0,
+            transport_head_len: 0,
+            src_port: 0,
+            dst_port: 0,
+            tcp_seq: 0,
+            tcp_flags: 0,
+            tunnel_pro: None,
+            inner: None,
+            application_pro: ApplicationPro::Unsupported,
+            application_state: LayerState::Unsupported,
+            application_start: 0,
         }
     }
 }
 
+// 协议层解析状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LayerState {
+    // 解析成功
+    Ok,
+    // 不支持的协议，或者上一层没有解析成功
+    Unsupported,
+    // 报文不完整，比如被snaplen截断
+    Truncated,
+    // 格式错误
+    Malformed,
+}
+
 // 链路层协议
 #[derive(Debug)]
 enum LinkPro {
@@ -57,15 +174,63 @@ enum LinkPro {
     LoopbackAddress,
     // 以太网
     Ethernet,
+    // Linux cooked capture v1，Linux下监听any
+    LinuxSll,
+    // Linux cooked capture v2，Linux下监听any
+    LinuxSll2,
     // 不支持的
     Unsupported,
 }
 
+// 报文方向，来自Linux cooked capture的packet type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PacketDirection {
+    // 发给本机
+    Host,
+    // 广播
+    Broadcast,
+    // 组播
+    Multicast,
+    // 发给其他主机，混杂模式下才能收到
+    OtherHost,
+    // 本机发出
+    Outgoing,
+    // 未知类型
+    Unknown(u16),
+}
+
+impl PacketDirection {
+    fn from_packet_type(packet_type: u16) -> Self {
+        match packet_type {
+            0 => PacketDirection::Host,
+            1 => PacketDirection::Broadcast,
+            2 => PacketDirection::Multicast,
+            3 => PacketDirection::OtherHost,
+            4 => PacketDirection::Outgoing,
+            _ => PacketDirection::Unknown(packet_type),
+        }
+    }
+
+    // 显示名称，和Linux的packet type对应
+    pub fn name(&self) -> String {
+        match self {
+            PacketDirection::Host => "host".to_string(),
+            PacketDirection::Broadcast => "broadcast".to_string(),
+            PacketDirection::Multicast => "multicast".to_string(),
+            PacketDirection::OtherHost => "otherhost".to_string(),
+            PacketDirection::Outgoing => "outgoing".to_string(),
+            PacketDirection::Unknown(packet_type) => format!("unknown({packet_type})"),
+        }
+    }
+}
+
 // 网络层协议
 #[derive(Debug)]
 enum NetworkPro {
     // IPv4
     IPv4,
+    // IPv6
+    IPv6,
     // 不支持的
     Unsupported,
 }
@@ -74,154 +239,479 @@ enum NetworkPro {
 #[derive(Debug)]
 pub enum TransportPro {
     TCP,
+    UDP,
+    // 只识别类型，不再解析应用层
+    ICMP,
+    ICMPv6,
+    // 隧道协议，直接承载在IP上
+    GRE,
     // 不支持的
     Unsupported,
 }
 
+// 隧道协议
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TunnelPro {
+    GRE,
+    VXLAN,
+    Geneve,
+}
+
 // 应用层协议
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ApplicationPro {
     HTTP,
+    // 以下基于UDP，根据端口识别
+    DNS,
+    Syslog,
+    StatsD,
+    QUIC,
     // 不支持的
     Unsupported,
 }
 
+// 读取大端u16，越界时返回None
+fn read_u16(data: &[u8], index: usize) -> Option<u16> {
+    let bytes = data.get(index..index + 2)?;
+    Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+// 读取大端u32，越界时返回None
+fn read_u32(data: &[u8], index: usize) -> Option<u32> {
+    let bytes = data.get(index..index + 4)?;
+    Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
 // 分析链路层协议
-unsafe fn analyze_link_with_linktype(
-    linktype: &pcap::Linktype,
-    pro_type: *mut ProType,
-    _data: &[u8],
-) {
-    if *linktype == pcap::Linktype::NULL {
+fn analyze_link_with_linktype(linktype: &pcap::Linktype, pro_type: &mut ProType, data: &[u8]) {
+    let (link_pro, head_len) = match linktype.0 {
         // 环回地址
-        (*pro_type).link_pro = LinkPro::LoopbackAddress;
-        (*pro_type).link_start = 0;
-        (*pro_type).link_head_len = LOOPBACK_ADDRESS_START.len();
+        0 => (LinkPro::LoopbackAddress, LOOPBACK_ADDRESS_START.len()),
+        // 以太网
+        1 => (LinkPro::Ethernet, ETHERNET_HEAD_LEN),
+        // mac os下，监听any网口，没有数据链路层
+        12 => (LinkPro::NotHave, 0),
+        // Linux cooked capture v1
+        113 => (LinkPro::LinuxSll, LINUX_SLL_HEAD_LEN),
+        // Linux cooked capture v2
+        276 => (LinkPro::LinuxSll2, LINUX_SLL2_HEAD_LEN),
+        // 未知协议
+        _ => return,
+    };
+    if data.len() < head_len {
+        pro_type.link_state = LayerState::Truncated;
         return;
     }
-    match linktype.0 {
-        1 => {
-            // 以太网
-            (*pro_type).link_pro = LinkPro::Ethernet;
-            (*pro_type).link_start = 0;
-            (*pro_type).link_head_len = 14;
-        }
-        12 => {
-            // mac os下，监听any网口，没有数据链路层
-            (*pro_type).link_pro = LinkPro::NotHave;
-            (*pro_type).link_start = 0;
-            (*pro_type).link_head_len = 0;
+    match link_pro {
+        LinkPro::LinuxSll => {
+            // packet type(2) + ARPHRD type(2) + 地址长度(2) + 地址(8) + 协议(2)
+            let packet_type = u16::from_be_bytes([data[0], data[1]]);
+            pro_type.direction = Some(PacketDirection::from_packet_type(packet_type));
         }
-        _ => {
-            // 未知协议
-            (*pro_type).link_pro = LinkPro::Unsupported;
-            (*pro_type).link_start = 0;
-            (*pro_type).link_head_len = 0;
+        LinkPro::LinuxSll2 => {
+            // 协议(2) + 保留(2) + 网口序号(4) + ARPHRD type(2) + packet type(1) + 地址长度(1) + 地址(8)
+            let packet_type = data[10] as u16;
+            pro_type.direction = Some(PacketDirection::from_packet_type(packet_type));
+            pro_type.if_index = read_u32(data, 4);
         }
+        _ => {}
     }
+    pro_type.link_pro = link_pro;
+    pro_type.link_state = LayerState::Ok;
+    pro_type.link_head_len = head_len;
 }
 
 // 分析网络层协议
-unsafe fn analyze_network(pro_type: *mut ProType, data: &[u8]) {
-    match (*pro_type).link_pro {
+fn analyze_network(pro_type: &mut ProType, data: &[u8]) {
+    pro_type.network_start = pro_type.link_start + pro_type.link_head_len;
+    if pro_type.link_state != LayerState::Ok {
+        return;
+    }
+    let link_start = pro_type.link_start;
+    match pro_type.link_pro {
         LinkPro::Ethernet => {
-            analyze_network_from_ethernet(pro_type, data);
+            // 以太网类型在目的MAC、源MAC之后
+            if let Some(pro) = read_u16(data, link_start + 12) {
+                analyze_network_with_ethertype(pro_type, pro, data);
+            }
         }
-        LinkPro::NotHave | LinkPro::LoopbackAddress => {
-            // 只要可以识别为IP，就认为是IP，复杂的以后再说
-            // 链路层没有记录协议，只要可以识别为IP，就认为是IP，复杂的以后再说
-            let start = (*pro_type).link_start + (*pro_type).link_head_len;
-            if !guess_ipv4(pro_type, start, data) {
-                (*pro_type).network_pro = NetworkPro::Unsupported;
-                (*pro_type).network_start = if let LinkPro::Unsupported = (*pro_type).link_pro {
-                    (*pro_type).link_start
-                } else {
-                    (*pro_type).link_start + (*pro_type).link_head_len
-                };
-                (*pro_type).network_head_len = 0;
+        LinkPro::LinuxSll => {
+            // 协议字段和以太网类型相同，SLL在头的最后
+            if let Some(pro) = read_u16(data, link_start + 14) {
+                analyze_network_with_ethertype(pro_type, pro, data);
+            }
+        }
+        LinkPro::LinuxSll2 => {
+            // 协议字段和以太网类型相同，SLL2在头的开始
+            if let Some(pro) = read_u16(data, link_start) {
+                analyze_network_with_ethertype(pro_type, pro, data);
             }
         }
-        _ => {
-            (*pro_type).network_pro = NetworkPro::Unsupported;
-            (*pro_type).network_start = if let LinkPro::Unsupported = (*pro_type).link_pro {
-                (*pro_type).link_start
-            } else {
-                (*pro_type).link_start + (*pro_type).link_head_len
-            };
-            (*pro_type).network_head_len = 0;
+        LinkPro::NotHave | LinkPro::LoopbackAddress => {
+            // 链路层没有记录协议，只要可以识别为IP，就认为是IP，复杂的以后再说
+            guess_ip(pro_type, data);
         }
+        LinkPro::Unsupported => {}
     }
 }
 
-// 根据以太网帧，分析网络层协议信息
-unsafe fn analyze_network_from_ethernet(pro_type: *mut ProType, data: &[u8]) {
-    let link_start = (*pro_type).link_start;
-    let link_head_len = (*pro_type).link_head_len;
-    (*pro_type).network_start = link_start + link_head_len;
-    let most = data[link_start + 12] as u16;
-    let least = data[link_start + 13] as u16;
-    let pro = most << 8 | least;
+// 根据版本号，猜测是IPv4还是IPv6
+fn guess_ip(pro_type: &mut ProType, data: &[u8]) {
+    match data.get(pro_type.network_start).map(|b| b >> 4) {
+        Some(4) => analyze_ipv4(pro_type, data),
+        Some(6) => analyze_ipv6(pro_type, data),
+        Some(_) => {}
+        None => pro_type.network_state = LayerState::Truncated,
+    }
+}
 
+// 根据以太网类型，分析网络层协议信息
+// 调用前需要设置网络层开始位置
+// VLAN标签、MPLS标签算作链路层，跳过后网络层开始位置后移
+fn analyze_network_with_ethertype(pro_type: &mut ProType, mut pro: u16, data: &[u8]) {
+    // 802.1Q、802.1ad，可能有多层，标签控制信息(2) + 以太网类型(2)
+    while let 0x8100 | 0x88A8 | 0x9100 = pro {
+        let start = pro_type.network_start;
+        let (Some(tci), Some(next_pro)) = (read_u16(data, start), read_u16(data, start + 2)) else {
+            pro_type.link_state = LayerState::Truncated;
+            return;
+        };
+        // 低12位是VLAN ID
+        pro_type.vlan_ids.push(tci & 0x0FFF);
+        pro_type.link_head_len += VLAN_TAG_LEN;
+        pro_type.network_start += VLAN_TAG_LEN;
+        pro = next_pro;
+    }
     match pro {
-        0x0800 => {
-            // IPv4
-            let head_len = (data[(*pro_type).network_start + 0] & 0x0F) * 4;
-            (*pro_type).network_pro = NetworkPro::IPv4;
-            (*pro_type).network_head_len = head_len as usize;
-        }
-        _ => {
-            (*pro_type).network_pro = NetworkPro::Unsupported;
-            (*pro_type).network_head_len = 0;
+        // IPv4
+        0x0800 => analyze_ipv4(pro_type, data),
+        // IPv6
+        0x86DD => analyze_ipv6(pro_type, data),
+        // MPLS单播、组播
+        0x8847 | 0x8848 => analyze_mpls(pro_type, data),
+        _ => {}
+    }
+}
+
+// 跳过MPLS标签栈，标签栈之后没有协议字段，根据版本号猜测IP
+fn analyze_mpls(pro_type: &mut ProType, data: &[u8]) {
+    loop {
+        let Some(entry) = read_u32(data, pro_type.network_start) else {
+            pro_type.link_state = LayerState::Truncated;
+            return;
+        };
+        // 标签(20) + 流量类别(3) + 栈底标志(1) + TTL(8)
+        pro_type.mpls_labels.push(entry >> 12);
+        pro_type.link_head_len += MPLS_LABEL_LEN;
+        pro_type.network_start += MPLS_LABEL_LEN;
+        if entry & 0x100 != 0 {
+            break;
         }
     }
+    guess_ip(pro_type, data);
 }
 
-// 猜测是否为ipv4，
-// 只要可以识别为IP，就认为是IP，复杂的以后再说
-unsafe fn guess_ipv4(pro_type: *mut ProType, start: usize, data: &[u8]) -> bool {
-    if data.len() < start {
-        return false;
+// 分析IPv4头
+fn analyze_ipv4(pro_type: &mut ProType, data: &[u8]) {
+    let start = pro_type.network_start;
+    pro_type.network_pro = NetworkPro::IPv4;
+    let Some(head) = data.get(start..start + IPV4_MIN_HEAD_LEN) else {
+        pro_type.network_state = LayerState::Truncated;
+        return;
+    };
+    let head_len = (head[0] & 0x0F) as usize * 4;
+    let total_len = u16::from_be_bytes([head[2], head[3]]) as usize;
+    if head[0] >> 4 != 4 || head_len < IPV4_MIN_HEAD_LEN || (total_len != 0 && total_len < head_len)
+    {
+        pro_type.network_state = LayerState::Malformed;
+        return;
     }
-    if data[start + 0] >> 4 != 4 {
-        return false;
+    if data.len() < start + head_len {
+        pro_type.network_state = LayerState::Truncated;
+        return;
     }
-    let head_len = (data[start + 0] & 0x0F) * 4;
-    if data.len() < head_len as usize {
-        return false;
+    pro_type.network_state = LayerState::Ok;
+    pro_type.network_head_len = head_len;
+    pro_type.next_pro = head[9];
+    pro_type.src_ip = Some(IpAddr::V4(Ipv4Addr::new(
+        head[12], head[13], head[14], head[15],
+    )));
+    pro_type.dst_ip = Some(IpAddr::V4(Ipv4Addr::new(
+        head[16], head[17], head[18], head[19],
+    )));
+    // TSO等情况下，总长度可能为0，这时以实际长度为准
+    if total_len != 0 {
+        pro_type.packet_end = data.len().min(start + total_len);
     }
-    (*pro_type).network_pro = NetworkPro::IPv4;
-    (*pro_type).network_start = start;
-    (*pro_type).network_head_len = head_len as usize;
-    true
 }
 
-// 分析传输层协议
-unsafe fn analyze_transport(pro_type: *mut ProType, data: &[u8]) {
-    if let NetworkPro::IPv4 = (*pro_type).network_pro {
-        (*pro_type).transport_start = (*pro_type).network_start + (*pro_type).network_head_len;
-        if data[(*pro_type).network_start + 9] == 0x06
-            && data.len() >= (*pro_type).transport_start + 20
-        {
-            (*pro_type).transport_pro = TransportPro::TCP;
-            (*pro_type).transport_head_len =
-                (data[(*pro_type).transport_start + 12] >> 4) as usize * 4;
-            return;
+// 分析IPv6头
+// 跳过扩展头，网络层头长度包含所有扩展头
+fn analyze_ipv6(pro_type: &mut ProType, data: &[u8]) {
+    let start = pro_type.network_start;
+    pro_type.network_pro = NetworkPro::IPv6;
+    let Some(head) = data.get(start..start + IPV6_HEAD_LEN) else {
+        pro_type.network_state = LayerState::Truncated;
+        return;
+    };
+    if head[0] >> 4 != 6 {
+        pro_type.network_state = LayerState::Malformed;
+        return;
+    }
+    let payload_len = u16::from_be_bytes([head[4], head[5]]) as usize;
+    let src_ip: [u8; 16] = head[8..24].try_into().unwrap_or_default();
+    let dst_ip: [u8; 16] = head[24..40].try_into().unwrap_or_default();
+    let mut next_pro = head[6];
+    let mut head_len = IPV6_HEAD_LEN;
+    // 遍历扩展头，找到传输层
+    loop {
+        let ext_start = start + head_len;
+        let ext_len = match next_pro {
+            // 逐跳选项、路由、目的选项、移动、HIP、Shim6，长度单位8字节，不含首个8字节
+            0 | 43 | 60 | 135 | 139 | 140 => {
+                data.get(ext_start + 1).map(|len| (*len as usize + 1) * 8)
+            }
+            // 分片，固定8字节
+            44 => Some(8),
+            // 认证头，长度单位4字节，不含首个8字节
+            51 => data.get(ext_start + 1).map(|len| (*len as usize + 2) * 4),
+            // 传输层或者无法继续解析的头（ESP加密、无下一个头）
+            _ => break,
+        };
+        match ext_len {
+            Some(ext_len) if data.len() >= ext_start + ext_len => {
+                next_pro = data[ext_start];
+                head_len += ext_len;
+            }
+            _ => {
+                pro_type.network_state = LayerState::Truncated;
+                return;
+            }
         }
     }
-    (*pro_type).transport_pro = TransportPro::Unsupported;
-    (*pro_type).transport_start = if let NetworkPro::Unsupported = (*pro_type).network_pro {
-        (*pro_type).network_start
-    } else {
-        (*pro_type).network_start + (*pro_type).network_head_len
+    // 负载长度包含扩展头
+    if payload_len != 0 && payload_len < head_len - IPV6_HEAD_LEN {
+        pro_type.network_state = LayerState::Malformed;
+        return;
+    }
+    pro_type.network_state = LayerState::Ok;
+    pro_type.network_head_len = head_len;
+    pro_type.next_pro = next_pro;
+    pro_type.src_ip = Some(IpAddr::V6(Ipv6Addr::from(src_ip)));
+    pro_type.dst_ip = Some(IpAddr::V6(Ipv6Addr::from(dst_ip)));
+    // 超大包（Jumbo Payload）时，负载长度为0，这时以实际长度为准
+    if payload_len != 0 {
+        pro_type.packet_end = data.len().min(start + IPV6_HEAD_LEN + payload_len);
+    }
+}
+
+// 分析传输层协议
+fn analyze_transport(pro_type: &mut ProType, data: &[u8]) {
+    pro_type.transport_start = pro_type.network_start + pro_type.network_head_len;
+    if pro_type.network_state != LayerState::Ok {
+        return;
+    }
+    let data = &data[..pro_type.packet_end];
+    match pro_type.next_pro {
+        0x01 => analyze_icmp(pro_type, TransportPro::ICMP, ICMP_HEAD_LEN, data),
+        0x06 => analyze_tcp(pro_type, data),
+        0x11 => analyze_udp(pro_type, data),
+        0x2F => analyze_gre(pro_type, data),
+        0x3A => analyze_icmp(pro_type, TransportPro::ICMPv6, ICMPV6_HEAD_LEN, data),
+        _ => {}
+    }
+}
+
+// 分析GRE头
+// 可选字段：校验和(4)、密钥(4)、序列号(4)，由标志位决定是否存在
+fn analyze_gre(pro_type: &mut ProType, data: &[u8]) {
+    let start = pro_type.transport_start;
+    pro_type.transport_pro = TransportPro::GRE;
+    let Some(flags) = read_u16(data, start) else {
+        pro_type.transport_state = LayerState::Truncated;
+        return;
+    };
+    // 只支持版本0，版本1是PPTP使用的增强GRE
+    if flags & 0x0007 != 0 {
+        pro_type.transport_state = LayerState::Malformed;
+        return;
+    }
+    let head_len = GRE_MIN_HEAD_LEN
+        + [0x8000, 0x2000, 0x1000]
+            .iter()
+            .filter(|bit| flags & *bit != 0)
+            .count()
+            * 4;
+    if data.len() < start + head_len {
+        pro_type.transport_state = LayerState::Truncated;
+        return;
+    }
+    pro_type.transport_state = LayerState::Ok;
+    pro_type.transport_head_len = head_len;
+    pro_type.tunnel_pro = Some(TunnelPro::GRE);
+}
+
+// 分析TCP头
+fn analyze_tcp(pro_type: &mut ProType, data: &[u8]) {
+    let start = pro_type.transport_start;
+    pro_type.transport_pro = TransportPro::TCP;
+    let Some(head) = data.get(start..start + TCP_MIN_HEAD_LEN) else {
+        pro_type.transport_state = LayerState::Truncated;
+        return;
+    };
+    let head_len = (head[12] >> 4) as usize * 4;
+    if head_len < TCP_MIN_HEAD_LEN {
+        pro_type.transport_state = LayerState::Malformed;
+        return;
+    }
+    if data.len() < start + head_len {
+        pro_type.transport_state = LayerState::Truncated;
+        return;
+    }
+    pro_type.transport_state = LayerState::Ok;
+    pro_type.transport_head_len = head_len;
+    pro_type.src_port = u16::from_be_bytes([head[0], head[1]]);
+    pro_type.dst_port = u16::from_be_bytes([head[2], head[3]]);
+    pro_type.tcp_seq = u32::from_be_bytes([head[4], head[5], head[6], head[7]]);
+    pro_type.tcp_flags = head[13];
+}
+
+// 分析UDP头
+fn analyze_udp(pro_type: &mut ProType, data: &[u8]) {
+    let start = pro_type.transport_start;
+    pro_type.transport_pro = TransportPro::UDP;
+    let Some(head) = data.get(start..start + UDP_HEAD_LEN) else {
+        pro_type.transport_state = LayerState::Truncated;
+        return;
+    };
+    // 长度包含UDP头
+    let udp_len = u16::from_be_bytes([head[4], head[5]]) as usize;
+    if udp_len != 0 && udp_len < UDP_HEAD_LEN {
+        pro_type.transport_state = LayerState::Malformed;
+        return;
+    }
+    pro_type.transport_state = LayerState::Ok;
+    pro_type.transport_head_len = UDP_HEAD_LEN;
+    pro_type.src_port = u16::from_be_bytes([head[0], head[1]]);
+    pro_type.dst_port = u16::from_be_bytes([head[2], head[3]]);
+    // IPv6超大包时，长度为0，这时以实际长度为准
+    if udp_len != 0 {
+        pro_type.packet_end = pro_type.packet_end.min(start + udp_len);
+    }
+    pro_type.tunnel_pro = match pro_type.dst_port {
+        VXLAN_PORT => Some(TunnelPro::VXLAN),
+        GENEVE_PORT => Some(TunnelPro::Geneve),
+        _ => None,
+    };
+}
+
+// 分析ICMP、ICMPv6头，只识别类型，不再解析应用层
+fn analyze_icmp(pro_type: &mut ProType, transport_pro: TransportPro, head_len: usize, data: &[u8]) {
+    pro_type.transport_pro = transport_pro;
+    if data.len() < pro_type.transport_start + head_len {
+        pro_type.transport_state = LayerState::Truncated;
+        return;
+    }
+    pro_type.transport_state = LayerState::Ok;
+    pro_type.transport_head_len = head_len;
+}
+
+// 分析网络层及以上的协议，有隧道时，递归分析内层报文
+fn analyze_upper(pro_type: &mut ProType, data: &[u8], depth: usize) {
+    analyze_network(pro_type, data);
+    analyze_transport(pro_type, data);
+    if depth < TUNNEL_MAX_DEPTH && pro_type.transport_state == LayerState::Ok {
+        analyze_tunnel(pro_type, data, depth);
+    }
+    analyze_application(pro_type, data);
+}
+
+// 分析隧道，找到内层报文的开始位置和类型，然后递归分析
+fn analyze_tunnel(pro_type: &mut ProType, data: &[u8], depth: usize) {
+    let Some(tunnel_pro) = pro_type.tunnel_pro else {
+        return;
+    };
+    let data = &data[..pro_type.packet_end];
+    // 隧道头开始位置，GRE是传输层头，VXLAN、Geneve在UDP之后
+    let start = pro_type.transport_start;
+    let payload_start = start + pro_type.transport_head_len;
+    // 内层报文开始位置、以太网类型，0x6558表示内层是以太网帧
+    let inner = match tunnel_pro {
+        TunnelPro::GRE => read_u16(data, start + 2).map(|pro| (payload_start, pro)),
+        TunnelPro::VXLAN => match data.get(payload_start) {
+            // 标志位I表示VNI有效
+            Some(flags) if flags & 0x08 != 0 => Some((payload_start + VXLAN_HEAD_LEN, 0x6558)),
+            _ => None,
+        },
+        TunnelPro::Geneve => match (data.get(payload_start), read_u16(data, payload_start + 2)) {
+            // 版本(2) + 选项长度(6)，选项长度单位4字节
+            (Some(head), Some(pro)) if head >> 6 == 0 => {
+                let head_len = GENEVE_MIN_HEAD_LEN + (head & 0x3F) as usize * 4;
+                Some((payload_start + head_len, pro))
+            }
+            _ => None,
+        },
     };
-    (*pro_type).transport_head_len = 0;
+    let Some((inner_start, pro)) = inner else {
+        return;
+    };
+    let mut inner = ProType::new(data.len());
+    inner.link_start = inner_start;
+    inner.link_state = LayerState::Ok;
+    match pro {
+        0x6558 if data.len() >= inner_start + ETHERNET_HEAD_LEN => {
+            inner.link_pro = LinkPro::Ethernet;
+            inner.link_head_len = ETHERNET_HEAD_LEN;
+        }
+        // 内层直接是IP，没有链路层
+        0x0800 | 0x86DD if data.len() > inner_start => inner.link_pro = LinkPro::NotHave,
+        0x6558 | 0x0800 | 0x86DD => inner.link_state = LayerState::Truncated,
+        _ => return,
+    }
+    analyze_upper(&mut inner, data, depth + 1);
+    pro_type.inner = Some(Box::new(inner));
 }
 
 // 分析应用层协议
-unsafe fn analyze_application(pro_type: *mut ProType, data: &[u8]) {
-    let start = (*pro_type).transport_start + (*pro_type).transport_head_len;
-    (*pro_type).application_start = start;
-    let payload = &data[start..];
+fn analyze_application(pro_type: &mut ProType, data: &[u8]) {
+    let start = pro_type.transport_start + pro_type.transport_head_len;
+    pro_type.application_start = start.min(pro_type.packet_end);
+    if pro_type.transport_state != LayerState::Ok || pro_type.inner.is_some() {
+        return;
+    }
+    let payload = &data[pro_type.application_start..pro_type.packet_end];
+    pro_type.application_pro = match pro_type.transport_pro {
+        TransportPro::TCP => analyze_application_payload(payload),
+        TransportPro::UDP => analyze_udp_application(pro_type.src_port, pro_type.dst_port, payload),
+        _ => ApplicationPro::Unsupported,
+    };
+    if pro_type.application_pro != ApplicationPro::Unsupported {
+        pro_type.application_state = LayerState::Ok;
+    }
+}
+
+// 根据端口和负载，识别UDP上的应用层协议
+fn analyze_udp_application(src_port: u16, dst_port: u16, payload: &[u8]) -> ApplicationPro {
+    let is_port = |port: u16| src_port == port || dst_port == port;
+    if (is_port(53) || is_port(5353)) && payload.len() >= 12 {
+        // DNS头固定12字节
+        ApplicationPro::DNS
+    } else if is_port(514) && payload.starts_with(b"<") {
+        // syslog以<PRI>开头
+        ApplicationPro::Syslog
+    } else if is_port(8125) {
+        ApplicationPro::StatsD
+    } else if is_port(443) && payload.first().is_some_and(|b| b & 0x40 != 0) {
+        // QUIC长头、短头的固定位都是1
+        ApplicationPro::QUIC
+    } else {
+        ApplicationPro::Unsupported
+    }
+}
+
+// 根据负载开头，识别应用层协议
+// TCP流重组后，也会用流的开头识别
+pub(crate) fn analyze_application_payload(payload: &[u8]) -> ApplicationPro {
     if payload.starts_with(b"GET")
         || payload.starts_with(b"POST")
         || payload.starts_with(b"PUT")
@@ -233,9 +723,482 @@ unsafe fn analyze_application(pro_type: *mut ProType, data: &[u8]) {
         || payload.starts_with(b"TRACE")
         || payload.starts_with(b"HTTP")
     {
-        (*pro_type).application_pro = ApplicationPro::HTTP;
-        return;
+        return ApplicationPro::HTTP;
     }
 
-    (*pro_type).application_pro = ApplicationPro::Unsupported;
+    ApplicationPro::Unsupported
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ETHERNET_IPV4: [u8; 14] = [0, 1, 2, 3, 4, 5, 0, 1, 2, 3, 4, 6, 0x08, 0x00];
+    const ETHERNET_IPV6: [u8; 14] = [0, 1, 2, 3, 4, 5, 0, 1, 2, 3, 4, 6, 0x86, 0xDD];
+
+    fn analyze(data: &[u8]) -> ProType {
+        ProType::from_with_linktype(&pcap::Linktype::ETHERNET, data)
+    }
+
+    // IPv4头，total_len包含IP头
+    fn ipv4(protocol: u8, total_len: u16) -> Vec<u8> {
+        let mut head = vec![
+            0x45, 0, 0, 0, 0, 0, 0, 0, 64, protocol, 0, 0, 10, 0, 0, 1, 10, 0, 0, 2,
+        ];
+        head[2..4].copy_from_slice(&total_len.to_be_bytes());
+        head
+    }
+
+    // IPv6头，payload_len包含扩展头
+    fn ipv6(next_pro: u8, payload_len: u16) -> Vec<u8> {
+        let mut head = vec![0x60, 0, 0, 0, 0, 0, next_pro, 64];
+        head[4..6].copy_from_slice(&payload_len.to_be_bytes());
+        head.extend_from_slice(&[0xfe; 16]);
+        head.extend_from_slice(&[0xfd; 16]);
+        head
+    }
+
+    fn tcp(data_offset: u8) -> Vec<u8> {
+        let mut head = vec![
+            0x13, 0x88, 0, 80, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0x18, 0x10, 0, 0, 0, 0, 0,
+        ];
+        head[12] = data_offset << 4;
+        head
+    }
+
+    fn udp(len: u16) -> Vec<u8> {
+        let mut head = vec![0x13, 0x88, 0, 53, 0, 0, 0, 0];
+        head[4..6].copy_from_slice(&len.to_be_bytes());
+        head
+    }
+
+    fn frame(parts: &[&[u8]]) -> Vec<u8> {
+        parts.concat()
+    }
+
+    fn states(pro_type: &ProType) -> [LayerState; 3] {
+        [
+            pro_type.link_state,
+            pro_type.network_state,
+            pro_type.transport_state,
+        ]
+    }
+
+    #[test]
+    fn truncated_ethernet() {
+        let pro_type = analyze(&ETHERNET_IPV4[..10]);
+        assert_eq!(pro_type.link_state, LayerState::Truncated);
+        assert_eq!(pro_type.network_state, LayerState::Unsupported);
+        assert_eq!(pro_type.transport_state, LayerState::Unsupported);
+    }
+
+    #[test]
+    fn truncated_ipv4() {
+        let data = frame(&[&ETHERNET_IPV4, &ipv4(6, 40)[..12]]);
+        assert_eq!(
+            states(&analyze(&data)),
+            [
+                LayerState::Ok,
+                LayerState::Truncated,
+                LayerState::Unsupported
+            ]
+        );
+
+        // 头长度24，只有20字节
+        let mut head = ipv4(6, 40);
+        head[0] = 0x46;
+        let data = frame(&[&ETHERNET_IPV4, &head]);
+        assert_eq!(analyze(&data).network_state, LayerState::Truncated);
+    }
+
+    #[test]
+    fn malformed_ipv4() {
+        let mut head = ipv4(6, 40);
+        head[0] = 0x44;
+        let data = frame(&[&ETHERNET_IPV4, &head, &tcp(5)]);
+        assert_eq!(analyze(&data).network_state, LayerState::Malformed);
+
+        // 总长度小于头长度
+        let data = frame(&[&ETHERNET_IPV4, &ipv4(6, 10), &tcp(5)]);
+        assert_eq!(analyze(&data).network_state, LayerState::Malformed);
+    }
+
+    #[test]
+    fn zero_total_len_ipv4() {
+        // TSO发出的报文总长度为0
+        let data = frame(&[&ETHERNET_IPV4, &ipv4(6, 0), &tcp(5), b"GET / HTTP/1.1"]);
+        let pro_type = analyze(&data);
+        assert_eq!(
+            states(&pro_type),
+            [LayerState::Ok, LayerState::Ok, LayerState::Ok]
+        );
+        assert_eq!(pro_type.packet_end, data.len());
+        assert_eq!(pro_type.application_start, 54);
+    }
+
+    #[test]
+    fn truncated_ipv6() {
+        let data = frame(&[&ETHERNET_IPV6, &ipv6(6, 20)[..30]]);
+        assert_eq!(
+            states(&analyze(&data)),
+            [
+                LayerState::Ok,
+                LayerState::Truncated,
+                LayerState::Unsupported
+            ]
+        );
+
+        // 逐跳选项头8字节，只有4字节
+        let data = frame(&[&ETHERNET_IPV6, &ipv6(0, 28), &[6, 0, 0, 0]]);
+        assert_eq!(analyze(&data).network_state, LayerState::Truncated);
+    }
+
+    #[test]
+    fn ipv6_payload_shorter_than_extension_headers() {
+        let hop_by_hop = [6, 0, 0, 0, 0, 0, 0, 0];
+        let data = frame(&[&ETHERNET_IPV6, &ipv6(0, 4), &hop_by_hop, &tcp(5)]);
+        let pro_type = analyze(&data);
+        assert_eq!(
+            states(&pro_type),
+            [
+                LayerState::Ok,
+                LayerState::Malformed,
+                LayerState::Unsupported
+            ]
+        );
+
+        let data = frame(&[&ETHERNET_IPV6, &ipv6(0, 28), &hop_by_hop, &tcp(5)]);
+        let pro_type = analyze(&data);
+        assert_eq!(
+            states(&pro_type),
+            [LayerState::Ok, LayerState::Ok, LayerState::Ok]
+        );
+        assert_eq!(pro_type.transport_start, 14 + 40 + 8);
+    }
+
+    #[test]
+    fn truncated_tcp() {
+        let data = frame(&[&ETHERNET_IPV4, &ipv4(6, 40), &tcp(5)[..10]]);
+        assert_eq!(
+            states(&analyze(&data)),
+            [LayerState::Ok, LayerState::Ok, LayerState::Truncated]
+        );
+
+        // 头长度32，只有20字节
+        let data = frame(&[&ETHERNET_IPV4, &ipv4(6, 52), &tcp(8)]);
+        assert_eq!(analyze(&data).transport_state, LayerState::Truncated);
+
+        let data = frame(&[&ETHERNET_IPV4, &ipv4(6, 40), &tcp(4)]);
+        assert_eq!(analyze(&data).transport_state, LayerState::Malformed);
+    }
+
+    #[test]
+    fn truncated_udp() {
+        let data = frame(&[&ETHERNET_IPV4, &ipv4(17, 28), &udp(8)[..4]]);
+        assert_eq!(
+            states(&analyze(&data)),
+            [LayerState::Ok, LayerState::Ok, LayerState::Truncated]
+        );
+
+        let data = frame(&[&ETHERNET_IPV4, &ipv4(17, 28), &udp(4)]);
+        assert_eq!(analyze(&data).transport_state, LayerState::Malformed);
+    }
+
+    #[test]
+    fn snaplen_cut_payload() {
+        // 原始报文有100字节负载，只抓到了GET
+        let data = frame(&[&ETHERNET_IPV4, &ipv4(6, 140), &tcp(5), b"GET"]);
+        let pro_type = analyze(&data);
+        assert_eq!(
+            states(&pro_type),
+            [LayerState::Ok, LayerState::Ok, LayerState::Ok]
+        );
+        assert_eq!(pro_type.packet_end, data.len());
+        assert_eq!(pro_type.application_start, 54);
+        assert_eq!(pro_type.application_pro, ApplicationPro::HTTP);
+    }
+
+    #[test]
+    fn every_prefix_without_panic() {
+        let hop_by_hop = [6, 0, 0, 0, 0, 0, 0, 0];
+        let frames = [
+            frame(&[&ETHERNET_IPV4, &ipv4(6, 45), &tcp(5), b"HTTP/"]),
+            frame(&[&ETHERNET_IPV4, &ipv4(17, 36), &udp(16), b"12345678"]),
+            frame(&[&ETHERNET_IPV6, &ipv6(0, 33), &hop_by_hop, &tcp(5), b"GET /"]),
+            // VLAN标签
+            frame(&[
+                &ETHERNET_IPV4[..12],
+                &[0x81, 0x00, 0x00, 0x64, 0x08, 0x00],
+                &ipv4(6, 40),
+                &tcp(5),
+            ]),
+        ];
+        for data in frames {
+            for len in 0..=data.len() {
+                analyze(&data[..len]);
+            }
+        }
+    }
+
+    // IPv4报文，总长度按负载计算
+    fn ipv4_packet(protocol: u8, payload: &[u8]) -> Vec<u8> {
+        frame(&[&ipv4(protocol, (20 + payload.len()) as u16), payload])
+    }
+
+    // UDP报文，目的端口决定隧道类型
+    fn udp_packet(dst_port: u16, payload: &[u8]) -> Vec<u8> {
+        let mut head = udp((8 + payload.len()) as u16);
+        head[2..4].copy_from_slice(&dst_port.to_be_bytes());
+        frame(&[&head, payload])
+    }
+
+    fn inner(pro_type: &ProType) -> &ProType {
+        pro_type.inner.as_deref().unwrap()
+    }
+
+    #[test]
+    fn gre_tunnel() {
+        let inner_ip = ipv4_packet(6, &tcp(5));
+        let data = frame(&[
+            &ETHERNET_IPV4,
+            &ipv4_packet(47, &frame(&[&[0x00, 0x00, 0x08, 0x00], &inner_ip])),
+        ]);
+        let pro_type = analyze(&data);
+        assert!(matches!(pro_type.transport_pro, TransportPro::GRE));
+        assert_eq!(pro_type.tunnel_pro, Some(TunnelPro::GRE));
+        let inner_type = inner(&pro_type);
+        assert_eq!(inner_type.link_start, 38);
+        assert_eq!(inner_type.network_start, 38);
+        assert_eq!(inner_type.transport_start, 58);
+        assert_eq!(
+            states(inner_type),
+            [LayerState::Ok, LayerState::Ok, LayerState::Ok]
+        );
+        assert_eq!(inner_type.src_port, 5000);
+        assert!(matches!(
+            pro_type.innermost().transport_pro,
+            TransportPro::TCP
+        ));
+
+        // 有密钥字段，头长度8
+        let data = frame(&[
+            &ETHERNET_IPV4,
+            &ipv4_packet(
+                47,
+                &frame(&[&[0x20, 0x00, 0x08, 0x00, 0, 0, 0, 1], &inner_ip]),
+            ),
+        ]);
+        let pro_type = analyze(&data);
+        assert_eq!(inner(&pro_type).network_start, 42);
+        assert_eq!(inner(&pro_type).transport_start, 62);
+    }
+
+    #[test]
+    fn vxlan_tunnel() {
+        let vxlan = frame(&[
+            &[0x08, 0, 0, 0, 0, 0, 1, 0],
+            &ETHERNET_IPV4,
+            &ipv4_packet(6, &tcp(5)),
+        ]);
+        let data = frame(&[&ETHERNET_IPV4, &ipv4_packet(17, &udp_packet(4789, &vxlan))]);
+        let pro_type = analyze(&data);
+        assert_eq!(pro_type.tunnel_pro, Some(TunnelPro::VXLAN));
+        let inner_type = inner(&pro_type);
+        assert_eq!(inner_type.link_start, 50);
+        assert_eq!(inner_type.network_start, 64);
+        assert_eq!(inner_type.transport_start, 84);
+        assert_eq!(
+            states(inner_type),
+            [LayerState::Ok, LayerState::Ok, LayerState::Ok]
+        );
+    }
+
+    #[test]
+    fn geneve_tunnel() {
+        // 选项长度1，4字节选项
+        let geneve = frame(&[
+            &[0x01, 0, 0x65, 0x58, 0, 0, 1, 0],
+            &[0; 4],
+            &ETHERNET_IPV4,
+            &ipv4_packet(6, &tcp(5)),
+        ]);
+        let data = frame(&[&ETHERNET_IPV4, &ipv4_packet(17, &udp_packet(6081, &geneve))]);
+        let pro_type = analyze(&data);
+        assert_eq!(pro_type.tunnel_pro, Some(TunnelPro::Geneve));
+        let inner_type = inner(&pro_type);
+        assert_eq!(inner_type.link_start, 54);
+        assert_eq!(inner_type.network_start, 68);
+        assert_eq!(inner_type.transport_start, 88);
+        assert_eq!(
+            states(inner_type),
+            [LayerState::Ok, LayerState::Ok, LayerState::Ok]
+        );
+    }
+
+    #[test]
+    fn truncated_tunnel() {
+        // GRE头只有2字节
+        let data = frame(&[&ETHERNET_IPV4, &ipv4_packet(47, &[0x00, 0x00])]);
+        let pro_type = analyze(&data);
+        assert_eq!(pro_type.transport_state, LayerState::Truncated);
+        assert!(pro_type.inner.is_none());
+
+        // GRE可选字段不完整
+        let data = frame(&[&ETHERNET_IPV4, &ipv4_packet(47, &[0x80, 0x00, 0x08, 0x00])]);
+        assert_eq!(analyze(&data).transport_state, LayerState::Truncated);
+
+        // VXLAN头之后的以太网头不完整
+        let vxlan = frame(&[&[0x08, 0, 0, 0, 0, 0, 1, 0], &ETHERNET_IPV4[..6]]);
+        let data = frame(&[&ETHERNET_IPV4, &ipv4_packet(17, &udp_packet(4789, &vxlan))]);
+        let pro_type = analyze(&data);
+        assert_eq!(inner(&pro_type).link_state, LayerState::Truncated);
+        assert_eq!(inner(&pro_type).network_state, LayerState::Unsupported);
+
+        // 没有VXLAN头、Geneve头
+        for port in [4789, 6081] {
+            let data = frame(&[&ETHERNET_IPV4, &ipv4_packet(17, &udp_packet(port, &[]))]);
+            let pro_type = analyze(&data);
+            assert_eq!(pro_type.transport_state, LayerState::Ok);
+            assert!(pro_type.inner.is_none());
+        }
+    }
+
+    fn analyze_link(linktype: i32, data: &[u8]) -> ProType {
+        ProType::from_with_linktype(&pcap::Linktype(linktype), data)
+    }
+
+    #[test]
+    fn linux_sll() {
+        // packet type(发出) + ARPHRD + 地址长度 + 地址 + 协议
+        let sll = [0, 4, 0, 1, 0, 6, 0, 1, 2, 3, 4, 5, 0, 0, 0x08, 0x00];
+        let data = frame(&[&sll, &ipv4(6, 40), &tcp(5)]);
+        let pro_type = analyze_link(113, &data);
+        assert_eq!(
+            states(&pro_type),
+            [LayerState::Ok, LayerState::Ok, LayerState::Ok]
+        );
+        assert_eq!(pro_type.network_start, 16);
+        assert_eq!(pro_type.transport_start, 36);
+        assert_eq!(pro_type.direction, Some(PacketDirection::Outgoing));
+        assert_eq!(pro_type.if_index, None);
+
+        let pro_type = analyze_link(113, &sll[..10]);
+        assert_eq!(pro_type.link_state, LayerState::Truncated);
+        assert_eq!(pro_type.direction, None);
+    }
+
+    #[test]
+    fn linux_sll2() {
+        // 协议 + 保留 + 网口序号 + ARPHRD + packet type(发给本机) + 地址长度 + 地址
+        let sll2 = [
+            0x86, 0xDD, 0, 0, 0, 0, 0, 3, 0, 1, 0, 6, 0, 1, 2, 3, 4, 5, 0, 0,
+        ];
+        let data = frame(&[&sll2, &ipv6(6, 20), &tcp(5)]);
+        let pro_type = analyze_link(276, &data);
+        assert_eq!(
+            states(&pro_type),
+            [LayerState::Ok, LayerState::Ok, LayerState::Ok]
+        );
+        assert_eq!(pro_type.network_start, 20);
+        assert_eq!(pro_type.transport_start, 60);
+        assert_eq!(pro_type.direction, Some(PacketDirection::Host));
+        assert_eq!(pro_type.if_index, Some(3));
+
+        // 未知的packet type
+        let mut sll2 = sll2;
+        sll2[10] = 9;
+        let data = frame(&[&sll2, &ipv6(6, 20), &tcp(5)]);
+        let pro_type = analyze_link(276, &data);
+        assert_eq!(pro_type.direction, Some(PacketDirection::Unknown(9)));
+        assert_eq!(pro_type.direction.unwrap().name(), "unknown(9)");
+
+        assert_eq!(
+            analyze_link(276, &sll2[..19]).link_state,
+            LayerState::Truncated
+        );
+    }
+
+    // MPLS标签：标签(20) + 流量类别(3) + 栈底标志(1) + TTL(8)
+    fn mpls(label: u32, bottom: bool) -> [u8; 4] {
+        (label << 12 | u32::from(bottom) << 8 | 64).to_be_bytes()
+    }
+
+    #[test]
+    fn qinq() {
+        // 802.1ad外层VLAN 100，802.1Q内层VLAN 200，优先级1
+        let data = frame(&[
+            &ETHERNET_IPV4[..12],
+            &[0x88, 0xA8, 0x00, 0x64],
+            &[0x81, 0x00, 0x20, 0xC8],
+            &[0x08, 0x00],
+            &ipv4(6, 40),
+            &tcp(5),
+        ]);
+        let pro_type = analyze(&data);
+        assert_eq!(
+            states(&pro_type),
+            [LayerState::Ok, LayerState::Ok, LayerState::Ok]
+        );
+        assert_eq!(pro_type.vlan_ids, [100, 200]);
+        assert_eq!(pro_type.network_start, 22);
+        assert_eq!(pro_type.transport_start, 42);
+
+        // 内层标签不完整
+        let pro_type = analyze(&data[..20]);
+        assert_eq!(pro_type.link_state, LayerState::Truncated);
+        assert_eq!(pro_type.vlan_ids, [100]);
+    }
+
+    #[test]
+    fn mpls_label_stack() {
+        let ethernet_mpls = [0, 1, 2, 3, 4, 5, 0, 1, 2, 3, 4, 6, 0x88, 0x47];
+        let data = frame(&[
+            &ethernet_mpls,
+            &mpls(16, false),
+            &mpls(0xFFFFF, true),
+            &ipv4(6, 40),
+            &tcp(5),
+        ]);
+        let pro_type = analyze(&data);
+        assert_eq!(
+            states(&pro_type),
+            [LayerState::Ok, LayerState::Ok, LayerState::Ok]
+        );
+        assert_eq!(pro_type.mpls_labels, [16, 0xFFFFF]);
+        assert_eq!(pro_type.network_start, 22);
+        assert_eq!(pro_type.transport_start, 42);
+
+        // 栈底之后按版本号识别IPv6
+        let data = frame(&[&ethernet_mpls, &mpls(100, true), &ipv6(6, 20), &tcp(5)]);
+        let pro_type = analyze(&data);
+        assert_eq!(pro_type.mpls_labels, [100]);
+        assert_eq!(
+            pro_type.src_ip,
+            Some(IpAddr::V6(Ipv6Addr::from([0xfe; 16])))
+        );
+        assert_eq!(pro_type.transport_start, 58);
+
+        // 没有栈底标志的标签，之后没有数据
+        let data = frame(&[&ethernet_mpls, &mpls(16, false), &mpls(17, false)]);
+        let pro_type = analyze(&data);
+        assert_eq!(pro_type.link_state, LayerState::Truncated);
+        assert_eq!(pro_type.mpls_labels, [16, 17]);
+        assert_eq!(pro_type.network_state, LayerState::Unsupported);
+    }
+
+    #[test]
+    fn vlan_then_mpls() {
+        let data = frame(&[
+            &ETHERNET_IPV4[..12],
+            &[0x81, 0x00, 0x00, 0x0A, 0x88, 0x47],
+            &mpls(16, true),
+            &ipv4(17, 28),
+            &udp(8),
+        ]);
+        let pro_type = analyze(&data);
+        assert_eq!(pro_type.vlan_ids, [10]);
+        assert_eq!(pro_type.mpls_labels, [16]);
+        assert_eq!(pro_type.network_start, 22);
+        assert_eq!(pro_type.transport_state, LayerState::Ok);
+    }
 }
@@ -2,7 +2,7 @@ use std::collections::HashMap;
 
 use crate::{
     analyze,
-    process::{OutPro, OutType},
+    process::{OutHead, OutPro, OutTime, OutTunnel, OutType, TimePrecision},
     DumpError, FilterArg, OutArg,
 };
 
@@ -29,7 +29,34 @@ pub fn read_arg(args: Vec<String>) -> Result<(FilterArg, OutArg), DumpError> {
     match filter_arg.application_pro {
         Some(analyze::ApplicationPro::HTTP) => {
             let pro_arg_http = http_arg::read_arg(&args)?;
+            if let Some(replay) = &pro_arg_http.replay {
+                if replay.target.is_empty() {
+                    return Err(DumpError {
+                        msg: "回放缺少服务端，需要用--http.replay指定".to_string(),
+                    });
+                }
+                if filter_arg.file_name.is_none() {
+                    return Err(DumpError {
+                        msg: "回放需要用-r指定pcap文件".to_string(),
+                    });
+                }
+            }
+            // 回放在按TCP流解析时进行
+            if pro_arg_http.replay.is_some() && !matches!(out_arg.out_pro, OutPro::Application) {
+                return Err(DumpError {
+                    msg: "回放需要按TCP流解析请求，-op只能是application".to_string(),
+                });
+            }
+            out_arg.pcap_redact = pro_arg_http
+                .redact
+                .as_ref()
+                .filter(|redact| redact.pcap)
+                .cloned();
             out_arg.pro_arg = pro_arg_http;
+            // 输出应用层时，按TCP流重组，避免报文不全
+            if let OutPro::Application = out_arg.out_pro {
+                out_arg.stream_pro = Some(analyze::ApplicationPro::HTTP);
+            }
         }
         _ => {}
     }
@@ -46,13 +73,26 @@ fn all_analyze_fn() -> HashMap<&'static str, ArgAnalyze> {
     map.insert("-p", port_analy);
     map.insert("--port", port_analy);
     map.insert("--bpf", bpf_analy);
+    map.insert("--vlan", vlan_analy);
     map.insert("-http", http_analy);
     map.insert("-https", http_analy);
+    map.insert("-dns", udp_pro_analy);
+    map.insert("-syslog", udp_pro_analy);
+    map.insert("-statsd", udp_pro_analy);
+    map.insert("-quic", udp_pro_analy);
     map.insert("-all", all_analy);
     map.insert("-ot", out_type_analy);
     map.insert("--outType", out_type_analy);
+    map.insert("-hl", hexdump_layers_analy);
+    map.insert("--hexdumpLayers", hexdump_layers_analy);
     map.insert("-op", out_pro_analy);
     map.insert("--outPro", out_pro_analy);
+    map.insert("--tunnel", out_tunnel_analy);
+    map.insert("-t", out_time_analy);
+    map.insert("--time", out_time_analy);
+    map.insert("--timePrecision", time_precision_analy);
+    map.insert("-oh", out_head_analy);
+    map.insert("--outHead", out_head_analy);
     map.insert("-of", out_file_analy);
     map.insert("--outFile", out_file_analy);
 
@@ -118,6 +158,7 @@ fn port_analy(
         });
     }
     filter_arg.port = Some(port.unwrap());
+    filter_arg.port_specified = true;
 
     Ok(index + 1)
 }
@@ -141,6 +182,32 @@ fn bpf_analy(
     Ok(index + 1)
 }
 
+// VLAN ID --vlan
+fn vlan_analy(
+    args: &Vec<String>,
+    index: usize,
+    filter_arg: &mut FilterArg,
+    _out_arg: &mut OutArg,
+) -> Result<usize, DumpError> {
+    if args.len() <= index + 1 {
+        // 正常是 --vlan 100 ，少了值
+        return Err(DumpError {
+            msg: "VLAN ID缺少值".to_string(),
+        });
+    }
+    let index = index + 1;
+    match args[index].parse() {
+        Ok(vlan) if vlan <= 4095 => filter_arg.vlan = Some(vlan),
+        _ => {
+            return Err(DumpError {
+                msg: "VLAN ID错误，仅支持0-4095".to_string(),
+            })
+        }
+    }
+
+    Ok(index + 1)
+}
+
 // -http -https
 fn http_analy(
     _args: &Vec<String>,
@@ -152,6 +219,28 @@ fn http_analy(
     Ok(index + 1)
 }
 
+// -dns -syslog -statsd -quic
+// 基于UDP的应用层协议，按照原值输出
+// 没有用-p指定端口时，使用协议的默认端口，替换http的默认端口
+fn udp_pro_analy(
+    args: &Vec<String>,
+    index: usize,
+    filter_arg: &mut FilterArg,
+    _out_arg: &mut OutArg,
+) -> Result<usize, DumpError> {
+    let (application_pro, port) = match args[index].as_str() {
+        "-dns" => (analyze::ApplicationPro::DNS, 53),
+        "-syslog" => (analyze::ApplicationPro::Syslog, 514),
+        "-statsd" => (analyze::ApplicationPro::StatsD, 8125),
+        _ => (analyze::ApplicationPro::QUIC, 443),
+    };
+    filter_arg.application_pro = Some(application_pro);
+    if !filter_arg.port_specified {
+        filter_arg.port = Some(port);
+    }
+    Ok(index + 1)
+}
+
 // -all
 fn all_analy(
     _args: &Vec<String>,
@@ -189,6 +278,110 @@ fn out_pro_analy(
     Ok(index + 1)
 }
 
+// 有隧道时，输出外层还是内层 --tunnel
+fn out_tunnel_analy(
+    args: &Vec<String>,
+    index: usize,
+    _filter_arg: &mut FilterArg,
+    out_arg: &mut OutArg,
+) -> Result<usize, DumpError> {
+    if args.len() <= index + 1 {
+        // 正常是 --tunnel outer ，少了值
+        return Err(DumpError {
+            msg: "隧道输出层缺少值".to_string(),
+        });
+    }
+    let index = index + 1;
+    match OutTunnel::from_name(&args[index]) {
+        Some(out_tunnel) => out_arg.out_tunnel = out_tunnel,
+        None => {
+            return Err(DumpError {
+                msg: "不支持的隧道输出层".to_string(),
+            })
+        }
+    }
+
+    Ok(index + 1)
+}
+
+// 抓包时间显示形式 -t --time
+fn out_time_analy(
+    args: &Vec<String>,
+    index: usize,
+    _filter_arg: &mut FilterArg,
+    out_arg: &mut OutArg,
+) -> Result<usize, DumpError> {
+    if args.len() <= index + 1 {
+        // 正常是 -t absolute ，少了值
+        return Err(DumpError {
+            msg: "时间显示形式缺少值".to_string(),
+        });
+    }
+    let index = index + 1;
+    match OutTime::from_name(&args[index]) {
+        Some(out_time) => out_arg.out_time = Some(out_time),
+        None => {
+            return Err(DumpError {
+                msg: "不支持的时间显示形式".to_string(),
+            })
+        }
+    }
+
+    Ok(index + 1)
+}
+
+// 抓包时间精度 --timePrecision
+fn time_precision_analy(
+    args: &Vec<String>,
+    index: usize,
+    _filter_arg: &mut FilterArg,
+    out_arg: &mut OutArg,
+) -> Result<usize, DumpError> {
+    if args.len() <= index + 1 {
+        // 正常是 --timePrecision ns ，少了值
+        return Err(DumpError {
+            msg: "时间精度缺少值".to_string(),
+        });
+    }
+    let index = index + 1;
+    match TimePrecision::from_name(&args[index]) {
+        Some(time_precision) => out_arg.time_precision = time_precision,
+        None => {
+            return Err(DumpError {
+                msg: "不支持的时间精度".to_string(),
+            })
+        }
+    }
+
+    Ok(index + 1)
+}
+
+// 输出说明行 -oh --outHead
+fn out_head_analy(
+    args: &Vec<String>,
+    index: usize,
+    _filter_arg: &mut FilterArg,
+    out_arg: &mut OutArg,
+) -> Result<usize, DumpError> {
+    if args.len() <= index + 1 {
+        // 正常是 -oh compact ，少了值
+        return Err(DumpError {
+            msg: "说明行形式缺少值".to_string(),
+        });
+    }
+    let index = index + 1;
+    match OutHead::from_name(&args[index]) {
+        Some(out_head) => out_arg.out_head = Some(out_head),
+        None => {
+            return Err(DumpError {
+                msg: "不支持的说明行形式".to_string(),
+            })
+        }
+    }
+
+    Ok(index + 1)
+}
+
 // 输出类型 -ot --outType
 fn out_type_analy(
     args: &Vec<String>,
@@ -215,6 +408,19 @@ fn out_type_analy(
     Ok(index + 1)
 }
 
+// hexdump输出时标记协议层 -hl --hexdumpLayers
+// 同时设置输出类型为hexdump
+fn hexdump_layers_analy(
+    _args: &Vec<String>,
+    index: usize,
+    _filter_arg: &mut FilterArg,
+    out_arg: &mut OutArg,
+) -> Result<usize, DumpError> {
+    out_arg.out_type = OutType::Hexdump;
+    out_arg.hexdump_layers = true;
+    Ok(index + 1)
+}
+
 // 生成pcap文件 -w
 fn pcap_file_name_analy(
     args: &Vec<String>,
@@ -253,3 +459,23 @@ fn out_file_analy(
     Ok(index + 1)
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn port(args: &[&str]) -> Option<u16> {
+        let args = args.iter().map(|arg| arg.to_string()).collect();
+        read_arg(args).unwrap().0.port
+    }
+
+    #[test]
+    fn udp_pro_default_port() {
+        assert_eq!(port(&[]), Some(80));
+        assert_eq!(port(&["-dns"]), Some(53));
+        assert_eq!(port(&["-quic"]), Some(443));
+        // 用-p指定的端口不会被替换，和参数顺序无关
+        assert_eq!(port(&["-p", "80", "-dns"]), Some(80));
+        assert_eq!(port(&["-dns", "-p", "5353"]), Some(5353));
+        assert_eq!(port(&["-p", "8126", "-statsd"]), Some(8126));
+    }
+}
@@ -1,6 +1,12 @@
 use std::collections::HashMap;
 
-use crate::{process::ProArgHttp, DumpError};
+use crate::{
+    process::{
+        charset_from_label, GrepTarget, HarWriter, HeaderFilter, HttpFilter, ObjectExport,
+        ProArgHttp, Redact, Replay,
+    },
+    DumpError,
+};
 
 type HttpArgAnalyze = fn(&Vec<String>, usize, &mut ProArgHttp) -> Result<usize, DumpError>;
 
@@ -18,6 +24,11 @@ pub(super) fn read_arg(args: &Vec<String>) -> Result<Box<ProArgHttp>, DumpError>
             index += 1;
         }
     }
+    if let Err(error) = pro_arg.filter.build_grep() {
+        return Err(DumpError {
+            msg: format!("搜索内容错误: {error}"),
+        });
+    }
 
     Ok(pro_arg)
 }
@@ -31,6 +42,37 @@ fn all_analyze_fn() -> HashMap<&'static str, HttpArgAnalyze> {
     map.insert("--http.hideBody", hide_body_analy);
     map.insert("-http.it", out_itself_analy);
     map.insert("--http.itself", out_itself_analy);
+    map.insert("-http.cs", charset_analy);
+    map.insert("--http.charset", charset_analy);
+    map.insert("--http.method", method_analy);
+    map.insert("--http.host", host_analy);
+    map.insert("--http.path", path_analy);
+    map.insert("--http.status", status_analy);
+    map.insert("--http.header", header_analy);
+    map.insert("--http.grep", grep_analy);
+    map.insert("-http.F", grep_fixed_analy);
+    map.insert("--http.fixed", grep_fixed_analy);
+    map.insert("-http.i", grep_ignore_case_analy);
+    map.insert("--http.ignoreCase", grep_ignore_case_analy);
+    map.insert("--http.grepIn", grep_target_analy);
+    map.insert("-http.rd", redact_analy);
+    map.insert("--http.redact", redact_analy);
+    map.insert("--http.redactHeader", redact_header_analy);
+    map.insert("--http.redactQuery", redact_query_analy);
+    map.insert("--http.redactJson", redact_json_analy);
+    map.insert("--http.redactPcap", redact_pcap_analy);
+    map.insert("-http.curl", curl_analy);
+    map.insert("--http.curl", curl_analy);
+    map.insert("--http.har", har_analy);
+    map.insert("--http.replay", replay_analy);
+    map.insert("--http.replayHeader", replay_header_analy);
+    map.insert("--http.replaySpeed", replay_speed_analy);
+    map.insert("--http.export", export_analy);
+    map.insert("--http.exportRequest", export_request_analy);
+    map.insert("-http.pt", pretty_analy);
+    map.insert("--http.pretty", pretty_analy);
+    map.insert("-http.hex", hexdump_analy);
+    map.insert("--http.hexdump", hexdump_analy);
 
     map
 }
@@ -55,7 +97,7 @@ fn hide_body_analy(
     Ok(index + 1)
 }
 
-// 输出数组 -http.it --http.itself  
+// 输出数组 -http.it --http.itself
 fn out_itself_analy(
     _args: &Vec<String>,
     index: usize,
@@ -65,3 +107,454 @@ fn out_itself_analy(
     Ok(index + 1)
 }
 
+// 格式化消息体 -http.pt --http.pretty
+fn pretty_analy(
+    _args: &Vec<String>,
+    index: usize,
+    pro_arg: &mut ProArgHttp,
+) -> Result<usize, DumpError> {
+    pro_arg.pretty = true;
+    Ok(index + 1)
+}
+
+// 二进制消息体显示hexdump -http.hex --http.hexdump
+fn hexdump_analy(
+    _args: &Vec<String>,
+    index: usize,
+    pro_arg: &mut ProArgHttp,
+) -> Result<usize, DumpError> {
+    pro_arg.hexdump = true;
+    Ok(index + 1)
+}
+
+// 强制使用的字符集 -http.cs --http.charset
+fn charset_analy(
+    args: &Vec<String>,
+    index: usize,
+    pro_arg: &mut ProArgHttp,
+) -> Result<usize, DumpError> {
+    if args.len() <= index + 1 {
+        // 正常是 --http.charset GBK ，少了值
+        return Err(DumpError {
+            msg: "字符集缺少值".to_string(),
+        });
+    }
+    let index = index + 1;
+    match charset_from_label(&args[index]) {
+        Some(encoding) => pro_arg.charset = Some(encoding),
+        None => {
+            return Err(DumpError {
+                msg: "不支持的字符集".to_string(),
+            })
+        }
+    }
+
+    Ok(index + 1)
+}
+
+// 过滤请求方法 --http.method POST,PUT
+fn method_analy(
+    args: &Vec<String>,
+    index: usize,
+    pro_arg: &mut ProArgHttp,
+) -> Result<usize, DumpError> {
+    if args.len() <= index + 1 {
+        // 正常是 --http.method POST,PUT ，少了值
+        return Err(DumpError {
+            msg: "请求方法缺少值".to_string(),
+        });
+    }
+    let index = index + 1;
+    let value = args[index].as_str();
+    pro_arg.filter.methods.extend(
+        value
+            .split(',')
+            .map(str::trim)
+            .filter(|method| !method.is_empty())
+            .map(String::from),
+    );
+    Ok(index + 1)
+}
+
+// 过滤主机 --http.host api.example.com，支持通配符
+fn host_analy(
+    args: &Vec<String>,
+    index: usize,
+    pro_arg: &mut ProArgHttp,
+) -> Result<usize, DumpError> {
+    if args.len() <= index + 1 {
+        // 正常是 --http.host api.example.com ，少了值
+        return Err(DumpError {
+            msg: "主机缺少值".to_string(),
+        });
+    }
+    let index = index + 1;
+    let value = args[index].as_str();
+    pro_arg.filter.host = Some(value.to_string());
+    Ok(index + 1)
+}
+
+// 过滤路径 --http.path '/v1/orders/*'，支持通配符
+fn path_analy(
+    args: &Vec<String>,
+    index: usize,
+    pro_arg: &mut ProArgHttp,
+) -> Result<usize, DumpError> {
+    if args.len() <= index + 1 {
+        // 正常是 --http.path '/v1/orders/*' ，少了值
+        return Err(DumpError {
+            msg: "路径缺少值".to_string(),
+        });
+    }
+    let index = index + 1;
+    let value = args[index].as_str();
+    pro_arg.filter.path = Some(value.to_string());
+    Ok(index + 1)
+}
+
+// 过滤状态码 --http.status 5xx，支持 404、5xx、200-299，逗号分隔
+fn status_analy(
+    args: &Vec<String>,
+    index: usize,
+    pro_arg: &mut ProArgHttp,
+) -> Result<usize, DumpError> {
+    if args.len() <= index + 1 {
+        // 正常是 --http.status 5xx ，少了值
+        return Err(DumpError {
+            msg: "状态码缺少值".to_string(),
+        });
+    }
+    let index = index + 1;
+    let value = args[index].as_str();
+    match HttpFilter::parse_status(value) {
+        Some(status) => pro_arg.filter.status.extend(status),
+        None => {
+            return Err(DumpError {
+                msg: "状态码错误，支持 404、5xx、200-299".to_string(),
+            })
+        }
+    }
+    Ok(index + 1)
+}
+
+// 过滤http头 --http.header 'X-Request-Id=~abc'，可以指定多个
+// Name: 有这个头，Name=value: 值相等，Name=~regex: 值匹配正则表达式
+fn header_analy(
+    args: &Vec<String>,
+    index: usize,
+    pro_arg: &mut ProArgHttp,
+) -> Result<usize, DumpError> {
+    if args.len() <= index + 1 {
+        // 正常是 --http.header 'X-Request-Id=~abc' ，少了值
+        return Err(DumpError {
+            msg: "http头过滤条件缺少值".to_string(),
+        });
+    }
+    let index = index + 1;
+    let value = args[index].as_str();
+    match HeaderFilter::parse(value) {
+        Ok(header_filter) => pro_arg.filter.headers.push(header_filter),
+        Err(error) => {
+            return Err(DumpError {
+                msg: format!("http头过滤条件错误: {error}"),
+            })
+        }
+    }
+    Ok(index + 1)
+}
+
+// 搜索内容 --http.grep 'order_id":\s*"123'，默认是正则表达式
+fn grep_analy(
+    args: &Vec<String>,
+    index: usize,
+    pro_arg: &mut ProArgHttp,
+) -> Result<usize, DumpError> {
+    if args.len() <= index + 1 {
+        // 正常是 --http.grep 'order_id":\s*"123' ，少了值
+        return Err(DumpError {
+            msg: "搜索内容缺少值".to_string(),
+        });
+    }
+    let index = index + 1;
+    let value = args[index].as_str();
+    pro_arg.filter.grep_pattern = Some(value.to_string());
+    Ok(index + 1)
+}
+
+// 按普通字符串搜索 -http.F --http.fixed
+fn grep_fixed_analy(
+    _args: &Vec<String>,
+    index: usize,
+    pro_arg: &mut ProArgHttp,
+) -> Result<usize, DumpError> {
+    pro_arg.filter.grep_fixed = true;
+    Ok(index + 1)
+}
+
+// 搜索时忽略大小写 -http.i --http.ignoreCase
+fn grep_ignore_case_analy(
+    _args: &Vec<String>,
+    index: usize,
+    pro_arg: &mut ProArgHttp,
+) -> Result<usize, DumpError> {
+    pro_arg.filter.grep_ignore_case = true;
+    Ok(index + 1)
+}
+
+// 搜索范围 --http.grepIn body
+fn grep_target_analy(
+    args: &Vec<String>,
+    index: usize,
+    pro_arg: &mut ProArgHttp,
+) -> Result<usize, DumpError> {
+    if args.len() <= index + 1 {
+        // 正常是 --http.grepIn body ，少了值
+        return Err(DumpError {
+            msg: "搜索范围缺少值".to_string(),
+        });
+    }
+    let index = index + 1;
+    let value = args[index].as_str();
+    match GrepTarget::from_name(value) {
+        Some(grep_target) => pro_arg.filter.grep_target = grep_target,
+        None => {
+            return Err(DumpError {
+                msg: "不支持的搜索范围".to_string(),
+            })
+        }
+    }
+    Ok(index + 1)
+}
+
+// 请求输出为curl命令 -http.curl --http.curl
+fn curl_analy(
+    _args: &Vec<String>,
+    index: usize,
+    pro_arg: &mut ProArgHttp,
+) -> Result<usize, DumpError> {
+    pro_arg.curl = true;
+    Ok(index + 1)
+}
+
+// 回放请求到指定的服务端 --http.replay 127.0.0.1:8080
+fn replay_analy(
+    args: &Vec<String>,
+    index: usize,
+    pro_arg: &mut ProArgHttp,
+) -> Result<usize, DumpError> {
+    if args.len() <= index + 1 {
+        // 正常是 --http.replay 127.0.0.1:8080 ，少了值
+        return Err(DumpError {
+            msg: "回放的服务端缺少值".to_string(),
+        });
+    }
+    let index = index + 1;
+    let value = args[index].as_str();
+    match &mut pro_arg.replay {
+        Some(replay) => replay.target = value.to_string(),
+        None => pro_arg.replay = Some(Replay::new(value.to_string())),
+    }
+    Ok(index + 1)
+}
+
+// 回放时改写http头 --http.replayHeader 'Authorization: Bearer xxx'，没有值时删除
+fn replay_header_analy(
+    args: &Vec<String>,
+    index: usize,
+    pro_arg: &mut ProArgHttp,
+) -> Result<usize, DumpError> {
+    if args.len() <= index + 1 {
+        // 正常是 --http.replayHeader 'Authorization: Bearer xxx' ，少了值
+        return Err(DumpError {
+            msg: "回放改写的http头缺少值".to_string(),
+        });
+    }
+    let index = index + 1;
+    let value = args[index].as_str();
+    let Some(header) = Replay::parse_header(value) else {
+        return Err(DumpError {
+            msg: "回放改写的http头格式错误，应该是 Name: value".to_string(),
+        });
+    };
+    pro_arg
+        .replay
+        .get_or_insert_with(|| Replay::new(String::new()))
+        .headers
+        .push(header);
+    Ok(index + 1)
+}
+
+// 回放速度倍数 --http.replaySpeed 2，0表示不等待
+fn replay_speed_analy(
+    args: &Vec<String>,
+    index: usize,
+    pro_arg: &mut ProArgHttp,
+) -> Result<usize, DumpError> {
+    if args.len() <= index + 1 {
+        // 正常是 --http.replaySpeed 2 ，少了值
+        return Err(DumpError {
+            msg: "回放速度缺少值".to_string(),
+        });
+    }
+    let index = index + 1;
+    let value = args[index].as_str();
+    let speed = match value.parse::<f64>() {
+        Ok(speed) if speed.is_finite() && speed >= 0.0 => speed,
+        _ => {
+            return Err(DumpError {
+                msg: "回放速度错误，需要是大于等于0的数字".to_string(),
+            })
+        }
+    };
+    pro_arg
+        .replay
+        .get_or_insert_with(|| Replay::new(String::new()))
+        .speed = speed;
+    Ok(index + 1)
+}
+
+// 请求和响应写入HAR文件 --http.har capture.har
+fn har_analy(
+    args: &Vec<String>,
+    index: usize,
+    pro_arg: &mut ProArgHttp,
+) -> Result<usize, DumpError> {
+    if args.len() <= index + 1 {
+        // 正常是 --http.har capture.har ，少了值
+        return Err(DumpError {
+            msg: "HAR文件名缺少值".to_string(),
+        });
+    }
+    let index = index + 1;
+    let value = args[index].as_str();
+    match HarWriter::create(value) {
+        Ok(har) => pro_arg.har = Some(har),
+        Err(error) => {
+            return Err(DumpError {
+                msg: format!("创建HAR文件失败: {error}"),
+            })
+        }
+    }
+    Ok(index + 1)
+}
+
+// 解码后的消息体导出到目录 --http.export objects
+fn export_analy(
+    args: &Vec<String>,
+    index: usize,
+    pro_arg: &mut ProArgHttp,
+) -> Result<usize, DumpError> {
+    if args.len() <= index + 1 {
+        // 正常是 --http.export objects ，少了值
+        return Err(DumpError {
+            msg: "导出目录缺少值".to_string(),
+        });
+    }
+    let index = index + 1;
+    let value = args[index].as_str();
+    match ObjectExport::create(value) {
+        Ok(export) => pro_arg.export = Some(export),
+        Err(error) => {
+            return Err(DumpError {
+                msg: format!("创建导出目录失败: {error}"),
+            })
+        }
+    }
+    Ok(index + 1)
+}
+
+// 导出消息体时，也导出请求体 --http.exportRequest
+fn export_request_analy(
+    _args: &Vec<String>,
+    index: usize,
+    pro_arg: &mut ProArgHttp,
+) -> Result<usize, DumpError> {
+    pro_arg.export_request = true;
+    Ok(index + 1)
+}
+
+// 敏感信息脱敏，使用默认的敏感头 -http.rd --http.redact
+fn redact_analy(
+    _args: &Vec<String>,
+    index: usize,
+    pro_arg: &mut ProArgHttp,
+) -> Result<usize, DumpError> {
+    pro_arg.redact.get_or_insert_with(Redact::new);
+    Ok(index + 1)
+}
+
+// 增加脱敏的http头 --http.redactHeader X-Token,X-Secret
+fn redact_header_analy(
+    args: &Vec<String>,
+    index: usize,
+    pro_arg: &mut ProArgHttp,
+) -> Result<usize, DumpError> {
+    if args.len() <= index + 1 {
+        // 正常是 --http.redactHeader X-Token,X-Secret ，少了值
+        return Err(DumpError {
+            msg: "脱敏的http头缺少值".to_string(),
+        });
+    }
+    let index = index + 1;
+    let value = args[index].as_str();
+    let redact = pro_arg.redact.get_or_insert_with(Redact::new);
+    redact.headers.extend(split_list(value));
+    Ok(index + 1)
+}
+
+// 脱敏的查询参数、表单字段 --http.redactQuery token,password
+fn redact_query_analy(
+    args: &Vec<String>,
+    index: usize,
+    pro_arg: &mut ProArgHttp,
+) -> Result<usize, DumpError> {
+    if args.len() <= index + 1 {
+        // 正常是 --http.redactQuery token,password ，少了值
+        return Err(DumpError {
+            msg: "脱敏的查询参数缺少值".to_string(),
+        });
+    }
+    let index = index + 1;
+    let value = args[index].as_str();
+    let redact = pro_arg.redact.get_or_insert_with(Redact::new);
+    redact.queries.extend(split_list(value));
+    Ok(index + 1)
+}
+
+// 脱敏的json字段 --http.redactJson password,user.phone,*.token
+fn redact_json_analy(
+    args: &Vec<String>,
+    index: usize,
+    pro_arg: &mut ProArgHttp,
+) -> Result<usize, DumpError> {
+    if args.len() <= index + 1 {
+        // 正常是 --http.redactJson password,user.phone,*.token ，少了值
+        return Err(DumpError {
+            msg: "脱敏的json字段缺少值".to_string(),
+        });
+    }
+    let index = index + 1;
+    let value = args[index].as_str();
+    let redact = pro_arg.redact.get_or_insert_with(Redact::new);
+    redact.json_fields.extend(split_list(value));
+    Ok(index + 1)
+}
+
+// 写pcap文件时也脱敏 --http.redactPcap
+fn redact_pcap_analy(
+    _args: &Vec<String>,
+    index: usize,
+    pro_arg: &mut ProArgHttp,
+) -> Result<usize, DumpError> {
+    pro_arg.redact.get_or_insert_with(Redact::new).pcap = true;
+    Ok(index + 1)
+}
+
+// 逗号分隔的列表
+fn split_list(value: &str) -> impl Iterator<Item = String> + '_ {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(String::from)
+}
@@ -6,13 +6,15 @@ mod listener;
 // 为了支持高级的过滤，在过滤数据前，就会分析协议
 // 不要在分析协议的代码里调用耗时长的方法
 mod analyze;
+// TCP流重组
+mod stream;
 // 数据加工
 mod process;
 
-use std::{error, fmt};
+use std::{error, fmt, time::Duration};
 
 pub use listener::FilterArg;
-pub use process::{OutArg, OutPro, OutType};
+pub use process::{OutArg, OutHead, OutPro, OutTime, OutTunnel, OutType, TimePrecision};
 // type GenericError = Box<dyn std::error::Error + Send + Sync + 'static>;
 
 // 自定义错误类型
@@ -32,11 +34,25 @@ impl error::Error for DumpError {}
 #[derive(Debug)]
 pub struct PacketInfo {
     pro_type: analyze::ProType,
+    // 抓包时间，从1970-01-01 00:00:00 UTC开始
+    ts: Duration,
+    // 实际抓到的长度
+    caplen: u32,
+    // 报文原始长度，大于caplen时，报文被截断
+    len: u32,
     data: Vec<u8>,
 }
 
 // 入口
 pub fn start(filter_arg: FilterArg, out_arg: OutArg) {
-    let receiver = listener::listener(filter_arg, &out_arg.pcap_file_name);
+    let tcp_stream = out_arg.stream_pro.is_some();
+    let nano = out_arg.time_precision == TimePrecision::Nano;
+    let receiver = listener::listener(
+        filter_arg,
+        tcp_stream,
+        nano,
+        &out_arg.pcap_file_name,
+        out_arg.pcap_redact.clone(),
+    );
     process::process(out_arg, &receiver);
 }
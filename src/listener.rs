@@ -1,25 +1,37 @@
 use std::{
     sync::mpsc::{self, Receiver, Sender},
     thread,
+    time::Duration,
 };
 
-use pcap::{Activated, Active, Capture, Device, Offline};
+use pcap::{Activated, Active, Capture, Device, Offline, Precision};
 
-use crate::{analyze, PacketInfo};
+use crate::{analyze, process::Redact, PacketInfo};
 
 pub use filter_arg::FilterArg;
 
 mod filter_arg;
 
-pub fn listener(filter_arg: FilterArg, pcap_file_name: &Option<String>) -> Receiver<PacketInfo> {
+// tcp_stream: 是否按TCP流重组，重组时TCP报文不按应用层协议过滤，在重组后过滤
+// nano: 是否按纳秒精度获取抓包时间
+// redact: 写pcap文件时的脱敏
+pub fn listener(
+    filter_arg: FilterArg,
+    tcp_stream: bool,
+    nano: bool,
+    pcap_file_name: &Option<String>,
+    redact: Option<Redact>,
+) -> Receiver<PacketInfo> {
     // sender: Sender<PacketInfo>
     let (sender, receiver) = mpsc::channel();
     if filter_arg.file_name.is_some() {
-        let mut capture = capture_from_file(&filter_arg);
+        let mut capture = capture_from_file(&filter_arg, nano);
         set_filter(&filter_arg, &mut capture);
-        thread::spawn(move || listening(&filter_arg, capture, sender, None));
+        thread::spawn(move || {
+            listening(&filter_arg, tcp_stream, nano, capture, sender, None, None)
+        });
     } else {
-        let mut capture = capture_from_device(&filter_arg);
+        let mut capture = capture_from_device(&filter_arg, nano);
         set_filter(&filter_arg, &mut capture);
         let save_file_option = if let Some(path) = pcap_file_name {
             let save_file = capture.savefile(path).unwrap();
@@ -27,14 +39,24 @@ pub fn listener(filter_arg: FilterArg, pcap_file_name: &Option<String>) -> Recei
         } else {
             None
         };
-        thread::spawn(move || listening(&filter_arg, capture, sender, save_file_option));
+        thread::spawn(move || {
+            listening(
+                &filter_arg,
+                tcp_stream,
+                nano,
+                capture,
+                sender,
+                save_file_option,
+                redact,
+            )
+        });
     };
     receiver
 }
 
 // 获取 Capture，从网口读数据
 // 就是操作句柄
-fn capture_from_device(filter_arg: &FilterArg) -> Capture<Active> {
+fn capture_from_device(filter_arg: &FilterArg, nano: bool) -> Capture<Active> {
     let device = Device::from(filter_arg.device_name.as_str());
     println!("device name: {}", device.name);
 
@@ -46,14 +68,25 @@ fn capture_from_device(filter_arg: &FilterArg) -> Capture<Active> {
         // .immediate_mode(true)
         // 超时
         .timeout(filter_arg.timeout)
+        .precision(precision(nano))
         .open()
         .unwrap()
 }
 
 // 获取Capture，从文件读数据
 // 就是操作句柄
-fn capture_from_file(filter_arg: &FilterArg) -> Capture<Offline> {
-    pcap::Capture::from_file(filter_arg.file_name.as_ref().unwrap()).unwrap()
+fn capture_from_file(filter_arg: &FilterArg, nano: bool) -> Capture<Offline> {
+    pcap::Capture::from_file_with_precision(filter_arg.file_name.as_ref().unwrap(), precision(nano))
+        .unwrap()
+}
+
+// 抓包时间精度，纳秒精度时，tv_usec中是纳秒
+fn precision(nano: bool) -> Precision {
+    if nano {
+        Precision::Nano
+    } else {
+        Precision::Micro
+    }
 }
 
 // 设置过滤器
@@ -78,28 +111,48 @@ fn set_filter<T: Activated + ?Sized>(filter_arg: &FilterArg, capture: &mut Captu
 // 开启监听
 fn listening<T: Activated + ?Sized>(
     filter_arg: &FilterArg,
+    tcp_stream: bool,
+    nano: bool,
     mut capture: Capture<T>,
     sender: Sender<PacketInfo>,
     mut save_file_option: Option<pcap::Savefile>,
+    redact: Option<Redact>,
 ) {
     let linktype = capture.get_datalink();
     loop {
         match capture.next_packet() {
             Ok(packet) => {
                 let pro_type = analyze::ProType::from_with_linktype(&linktype, packet.data);
-                if !filter(filter_arg, &pro_type) {
+                if !filter(filter_arg, tcp_stream, &pro_type) {
                     // 不是目标
                     continue;
                 }
+                // 脱敏后的报文，写pcap文件用
+                let redacted = redact
+                    .as_ref()
+                    .filter(|_| save_file_option.is_some())
+                    .map(|redact| redact_packet(redact, &pro_type, packet.data));
+                let ts = packet.header.ts;
+                let nanos = if nano {
+                    ts.tv_usec as u32
+                } else {
+                    ts.tv_usec as u32 * 1000
+                };
                 let packet_info = PacketInfo {
                     pro_type,
+                    ts: Duration::new(ts.tv_sec as u64, nanos),
+                    caplen: packet.header.caplen,
+                    len: packet.header.len,
                     data: Vec::from(packet.data),
                 };
                 if let Err(_) = sender.send(packet_info) {
                     break;
                 }
                 if let Some(save_file) = save_file_option.as_mut() {
-                    save_file.write(&packet);
+                    match &redacted {
+                        Some(data) => save_file.write(&pcap::Packet::new(packet.header, data)),
+                        None => save_file.write(&packet),
+                    }
                     let _ = save_file.flush();
                 }
             }
@@ -118,10 +171,37 @@ fn listening<T: Activated + ?Sized>(
     }
 }
 
+// 报文脱敏，只处理最内层的应用层数据，长度不变
+// 只修改负载，TCP校验和不再更新
+fn redact_packet(redact: &Redact, pro_type: &analyze::ProType, data: &[u8]) -> Vec<u8> {
+    let mut data = data.to_vec();
+    let inner_pro_type = pro_type.innermost();
+    if matches!(inner_pro_type.transport_pro, analyze::TransportPro::TCP) {
+        let (start, end) = (inner_pro_type.application_start, inner_pro_type.packet_end);
+        if let Some(payload) = data.get_mut(start..end) {
+            redact.payload(payload);
+        }
+    }
+    data
+}
+
 // 进一步自定义过滤
-fn filter(filter_arg: &FilterArg, pro_type: &analyze::ProType) -> bool {
+fn filter(filter_arg: &FilterArg, tcp_stream: bool, pro_type: &analyze::ProType) -> bool {
+    let inner_pro_type = pro_type.innermost();
     if let Some(application_pro) = &filter_arg.application_pro {
-        if *application_pro != pro_type.application_pro {
+        // 有隧道时，应用层在最内层
+        // 按TCP流重组时，后续的报文段识别不出应用层协议，重组后再过滤
+        let stream =
+            tcp_stream && matches!(inner_pro_type.transport_pro, analyze::TransportPro::TCP);
+        if !stream && *application_pro != inner_pro_type.application_pro {
+            return false;
+        }
+    }
+    if let Some(vlan) = filter_arg.vlan {
+        if !pro_type
+            .layers()
+            .any(|pro_type| pro_type.vlan_ids.contains(&vlan))
+        {
             return false;
         }
     }
@@ -13,8 +13,12 @@ pub struct FilterArg {
     // 应用层协议，HTTP什么的
     pub application_pro: Option<analyze::ApplicationPro>,
     pub port: Option<u16>,
+    // 是否用-p指定了端口，没有指定时使用应用层协议的默认端口
+    pub port_specified: bool,
     // BPF过滤条件
     pub bpf: Option<String>,
+    // VLAN ID，解析后过滤，任意一层VLAN标签匹配即可
+    pub vlan: Option<u16>,
     pub timeout: i32,
 }
 
@@ -25,7 +29,9 @@ impl FilterArg {
             file_name: None,
             application_pro: Some(analyze::ApplicationPro::HTTP),
             port: Some(80),
+            port_specified: false,
             bpf: None,
+            vlan: None,
             timeout: 200,
         };
         filter_arg
@@ -20,14 +20,45 @@ fn show_print() {
 -w                          生成pcap文件。文件格式同tcpdump命令-w参数生成的文件。不受-op、-ot等输出相关参数控制
 -p --port                   端口号
 --bpf                       BPF过滤条件
--http -https                过滤应用层是http(s)协议的数据，按照http(s)协议输出报文，默认值
--all                        不过滤应用层
+--vlan                      VLAN ID，解析报文后过滤，QinQ时任意一层匹配即可。不修改BPF条件，读取带VLAN标签的文件时，BPF需要自行加上vlan关键字
+-http -https                过滤应用层是http(s)协议的数据，按照http(s)协议输出报文，默认值。输出应用层时，按TCP流重组
+-dns -syslog -statsd -quic  过滤应用层是对应协议的数据（基于UDP，根据端口识别），没有用-p指定端口时，使用默认端口53、514、8125、443
+-all                        不过滤应用层，UDP报文也会按层输出
 -op --outPro                输出协议层，支持值域: link(链路层)，network(网络层)，transport(传输层)，application(应用层)，默认值：application
--ot --outType               输出类型，会在应用层控制后转换，支持值域: itself(原值)，decimal(10进制数组)，hexadecimal(16进制数组)，默认值：itself
+--tunnel                    有隧道(GRE、VXLAN、Geneve)时，-op对应的报文层，支持值域: outer(外层)，inner(内层)，默认值：inner
+-ot --outType               输出类型，会在应用层控制后转换，支持值域: itself(原值)，decimal(10进制数组)，hexadecimal(16进制数组)，hexdump(和hexdump -C相同，带偏移量和ASCII)，json(每个报文或者http消息一行json，包含时间、地址、协议、各层位置、数据，二进制数据用base64编码)，默认值：itself
+-hl --hexdumpLayers         hexdump输出时，在行尾标记IP、TCP、应用层等协议层的开始位置，同时设置输出类型为hexdump。只在逐个报文输出时生效，应用层控制改变了数据时不标记
+-t --time                   显示抓包时间，支持值域: absolute(绝对时间)，relative(相对第一个报文)，delta(相对上一次输出)。逐个报文输出时，同时显示抓包长度和原始长度
+--timePrecision             抓包时间精度，支持值域: us(微秒)，ns(纳秒)，默认值：us
+-oh --outHead               每条输出前显示说明行(源地址、目的地址、协议、TCP标志位、长度、时间)，支持值域: compact(简洁)，verbose(详细)。不指定则不显示，没有-t时显示绝对时间
 -of --outFile               输出文件，不指定则输出到标准输出
 -http.hh --http.hideHead    隐藏http头，当指定应用层是http时生效
 -http.hb --http.hideBody    隐藏http体，当指定应用层是http时生效
 -http.it --http.itself      输出数组
+-http.pt --http.pretty      按Content-Type格式化消息体，支持json、xml、html、表单(application/x-www-form-urlencoded)，指定-http.it时不生效
+-http.hex --http.hexdump    二进制消息体(图片、protobuf等)显示hexdump -C格式的开头部分，不指定时显示摘要(类型、大小、SHA-256)
+--http.method               过滤请求方法，逗号分隔，比如 POST,PUT。以下http过滤条件，配对后对请求和响应一起判断
+--http.host                 过滤主机，支持通配符，比如 *.example.com
+--http.path                 过滤路径，不含查询参数，支持通配符，比如 '/v1/orders/*'
+--http.status               过滤状态码，逗号分隔，支持 404、5xx、200-299
+--http.header               过滤http头，可以指定多个。Name(有这个头)，Name=value(值相等)，Name=~regex(值匹配正则表达式)
+--http.grep                 搜索内容，默认是正则表达式，请求和响应任意一个满足即可。消息体在分块合并、解压、字符集转换后搜索
+-http.F --http.fixed        搜索内容按普通字符串处理
+-http.i --http.ignoreCase   搜索时忽略大小写
+--http.grepIn               搜索范围，支持值域: body(消息体)，head(http头)，all(http头和消息体)，默认值：body
+-http.curl --http.curl      每个请求输出为可以执行的curl命令，不输出响应，http过滤条件和脱敏仍然生效。二进制请求体用printf通过管道传给curl
+--http.replay               读取-r指定的pcap文件，把其中的http请求回放到指定的服务端(主机:端口)，输出回放和抓包时的状态码、耗时差异。每个请求使用新的连接，按抓包时的请求顺序在请求完成时发送，只用请求判断过滤条件。需要-op application
+--http.replayHeader         回放时改写http头，可以指定多个。Name: value(替换或者增加)，Name:(删除)
+--http.replaySpeed          回放速度倍数，默认值：1(按照抓包时的请求间隔)，2(两倍速)，0(不等待)
+--http.har                  请求和响应写入HAR 1.2文件，可以在浏览器开发者工具中打开。输出应用层时生效，包含http头、查询参数、解码后的消息体(二进制用base64)、耗时、服务端IP
+--http.export               解码后的响应体导出到目录，文件名取自url路径，没有扩展名时根据Content-Type补充，重名时加序号。index.tsv记录文件和请求的对应关系。输出应用层时生效
+--http.exportRequest        导出消息体时，也导出请求体，文件名带.request
+-http.rd --http.redact      敏感信息脱敏，显示为******。默认脱敏的http头: Authorization、Proxy-Authorization、Cookie、Set-Cookie、X-Api-Key、Api-Key、X-Auth-Token、X-Access-Token、X-Csrf-Token
+--http.redactHeader         增加脱敏的http头，逗号分隔。以下脱敏参数都会开启脱敏
+--http.redactQuery          脱敏的查询参数，也用于表单消息体，逗号分隔，比如 token,password
+--http.redactJson           脱敏的json字段，逗号分隔。没有.时匹配任意层级的同名字段，有.时从根开始匹配，*匹配一层，比如 password,user.phone,*.token
+--http.redactPcap           -w写pcap文件时也脱敏，长度不变，用*覆盖，TCP校验和不再更新。压缩的消息体、跨报文的字段不处理
+-http.cs --http.charset     强制使用的字符集，比如GBK、GB18030、Big5、Shift_JIS、ISO-8859-1、UTF-16。不指定时使用Content-Type中的字符集，都没有时自动猜测
 "#;
     print!("{help}");
 }
@@ -1,10 +1,17 @@
-use std::sync::mpsc::Receiver;
+use std::{borrow::Cow, fs::File, sync::mpsc::Receiver, time::Duration};
 
-pub use out_arg::{OutArg, OutPro, OutType};
+pub use out_arg::{OutArg, OutHead, OutPro, OutTime, OutTunnel, OutType, TimePrecision};
 
-use crate::PacketInfo;
+use crate::{
+    analyze::TransportPro,
+    stream::{Reassembler, StreamEvent, StreamKey},
+    PacketInfo,
+};
 
-pub use pro_data::ProArgHttp;
+pub use pro_data::{
+    charset_from_label, GrepTarget, HarWriter, HeaderFilter, HttpFilter, ObjectExport, ProArgHttp,
+    Redact, Replay,
+};
 
 // 输出控制参数
 mod out_arg;
@@ -14,10 +21,16 @@ mod get_pro_data;
 pub(crate) mod change_data;
 // 输出数据
 mod out_data;
+// 抓包时间显示
+mod out_time;
+// 输出说明行
+mod out_head;
+// json输出
+mod out_json;
 // 根据协议进行数据处理
 mod pro_data;
 
-pub fn process(out_arg: OutArg, receiver: &Receiver<PacketInfo>) {
+pub fn process(mut out_arg: OutArg, receiver: &Receiver<PacketInfo>) {
     if out_arg.pcap_file_name.is_some() {
         for _ in receiver {}
         return;
@@ -26,12 +39,153 @@ pub fn process(out_arg: OutArg, receiver: &Receiver<PacketInfo>) {
     let change_data = change_data::change_data_fn(&out_arg);
     let mut out_file = out_data::get_file_handle(&out_arg);
     let out_data = out_data::out_data_fn(&out_arg);
+    // head: 数据前单独一行的说明，不做数据转换
+    let out_changed = |head: Option<String>, data: &[u8], out_file: &mut Option<File>| {
+        if let Some(head) = head {
+            out_data(head.as_bytes(), out_file);
+            out_data(b"\n", out_file);
+        }
+        out_data(data, out_file);
+        out_data(b"\n\n", out_file);
+    };
+    let out = |head: Option<String>, data: &[u8], out_file: &mut Option<File>| {
+        out_changed(head, &change_data(data), out_file);
+    };
+    // hexdump时标记协议层
+    let hexdump_layers = out_arg.hexdump_layers && matches!(out_arg.out_type, OutType::Hexdump);
+    // json输出时每个对象一行，不输出说明行，也不做数据转换
+    let json = matches!(out_arg.out_type, OutType::Json);
+    // 协议控制直接输出json对象，否则按报文或者TCP流包装
+    let pro_json = json && out_arg.pro_arg.json();
+    let out_json = |data: &[u8], out_file: &mut Option<File>| {
+        out_data(data, out_file);
+        out_data(b"\n", out_file);
+    };
+    let mut time_show = out_time::TimeShow::new(&out_arg);
+    let mut reassembler = Reassembler::new();
+    let mut last_ts = Duration::ZERO;
 
     for packet_info in receiver {
-        let data = get_pro_data(&packet_info);
+        time_show.receive(packet_info.ts);
+        last_ts = packet_info.ts;
+        let inner_pro_type = packet_info.pro_type.innermost();
+        if out_arg.stream_pro.is_some() && matches!(inner_pro_type.transport_pro, TransportPro::TCP)
+        {
+            let events = reassembler.push(&packet_info, inner_pro_type);
+            for (key, data) in stream_process(&mut out_arg, events) {
+                let time = time_show.show(packet_info.ts);
+                if pro_json {
+                    out_json(&data, &mut out_file);
+                } else if json {
+                    let data = out_json::stream_json(
+                        &key,
+                        out_arg.stream_pro,
+                        packet_info.ts,
+                        time,
+                        &data,
+                    );
+                    out_json(data.as_bytes(), &mut out_file);
+                } else {
+                    let head = stream_head(&out_arg, &key, time, &data);
+                    out(head, &data, &mut out_file);
+                }
+            }
+            continue;
+        }
+        let pro_type = out_arg.out_tunnel.select(&packet_info.pro_type);
+        let data = get_pro_data(&packet_info.data, pro_type);
+        let data_len = data.len();
         let data = out_arg.pro_arg.byte_process(data);
-        let data = change_data(&data);
-        out_data(&data, &mut out_file);
-        out_data(b"\n\n", &mut out_file);
+        if data.is_empty() && data_len > 0 {
+            // 被协议控制过滤掉
+            continue;
+        }
+        let time = time_show.show(packet_info.ts);
+        if json {
+            let data = out_json::packet_json(&packet_info, pro_type, time, &data);
+            out_json(data.as_bytes(), &mut out_file);
+            continue;
+        }
+        let head = match out_arg.out_head {
+            Some(out_head) => Some(out_head::packet_head(
+                out_head,
+                &packet_info,
+                pro_type,
+                time,
+                data_len,
+            )),
+            None => time.map(|time| {
+                format!(
+                    "{time}  抓包长度: {}  原始长度: {}",
+                    packet_info.caplen, packet_info.len
+                )
+            }),
+        };
+        // 应用层控制改变了数据时，位置对应不上
+        if hexdump_layers && matches!(data, Cow::Borrowed(_)) {
+            let start = get_pro_data::pro_start(&out_arg.out_pro, pro_type);
+            let marks = change_data::layer_marks(&packet_info.pro_type, start, data.len());
+            let data = change_data::hexdump_marked(&data, &marks);
+            out_changed(head, data.as_bytes(), &mut out_file);
+            continue;
+        }
+        out(head, &data, &mut out_file);
     }
+    for (key, data) in stream_process(&mut out_arg, reassembler.finish()) {
+        let time = time_show.show(last_ts);
+        if pro_json {
+            out_json(&data, &mut out_file);
+        } else if json {
+            let data = out_json::stream_json(&key, out_arg.stream_pro, last_ts, time, &data);
+            out_json(data.as_bytes(), &mut out_file);
+        } else {
+            let head = stream_head(&out_arg, &key, time, &data);
+            out(head, &data, &mut out_file);
+        }
+    }
+}
+
+// TCP流重组后输出的说明行
+fn stream_head(
+    out_arg: &OutArg,
+    key: &StreamKey,
+    time: Option<String>,
+    data: &[u8],
+) -> Option<String> {
+    match (out_arg.out_head, out_arg.stream_pro) {
+        (Some(out_head), Some(stream_pro)) => Some(out_head::stream_head(
+            out_head,
+            key,
+            stream_pro,
+            time,
+            data.len(),
+        )),
+        _ => time,
+    }
+}
+
+// 处理TCP流重组后的事件，只处理应用层协议符合的连接
+// 返回触发输出的数据方向和输出数据
+fn stream_process(out_arg: &mut OutArg, events: Vec<StreamEvent>) -> Vec<(StreamKey, Vec<u8>)> {
+    let mut out_datas = Vec::new();
+    for event in events {
+        match event {
+            StreamEvent::Data {
+                key,
+                application_pro,
+                ts,
+                data,
+            } => {
+                if Some(application_pro) == out_arg.stream_pro {
+                    let datas = out_arg.pro_arg.stream_process(&key, ts, &data);
+                    out_datas.extend(datas.into_iter().map(|data| (key, data)));
+                }
+            }
+            StreamEvent::Close { key, reason } => {
+                let datas = out_arg.pro_arg.stream_close(&key, reason);
+                out_datas.extend(datas.into_iter().map(|data| (key, data)));
+            }
+        }
+    }
+    out_datas
 }
@@ -1,12 +1,17 @@
 use std::borrow::Cow;
 
+use crate::analyze::{ApplicationPro, LayerState, ProType, TransportPro, TunnelPro};
+
 use super::out_arg;
 
+const BASE64_CHARS: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
 // 数据转换
 pub(super) fn change_data_fn(out_arg: &out_arg::OutArg) -> fn(&[u8]) -> Cow<'_, [u8]> {
     match out_arg.out_type {
         out_arg::OutType::Decimal => u8_to_10,
         out_arg::OutType::Hexadecimal => u8_to_16,
+        out_arg::OutType::Hexdump => u8_to_hexdump,
         _ => |x| Cow::Borrowed(x),
     }
 }
@@ -28,3 +33,232 @@ pub(crate) fn u8_to_16(data: &[u8]) -> Cow<'_, [u8]> {
     let data = format!("[{data}]");
     Cow::Owned(data.into_bytes())
 }
+
+// 和 hexdump -C 相同的格式
+pub(crate) fn u8_to_hexdump(data: &[u8]) -> Cow<'_, [u8]> {
+    Cow::Owned(hexdump(data).into_bytes())
+}
+
+// 和 hexdump -C 相同的格式，每行16字节，带偏移量和ASCII
+// 00000000  48 54 54 50 2f 31 2e 31  20 32 30 30 20 4f 4b 0d  |HTTP/1.1 200 OK.|
+pub(crate) fn hexdump(data: &[u8]) -> String {
+    hexdump_marked(data, &[])
+}
+
+// hexdump，在行尾标记从这一行开始的协议层
+// marks: 协议层在data中的开始位置和名称，按位置排序
+// 00000000  45 00 00 3c 1c 46 40 00  40 06 b1 e6 ac 10 0a 63  |E..<.F@.@......c|  # IPv4@0x00
+pub(crate) fn hexdump_marked(data: &[u8], marks: &[(usize, String)]) -> String {
+    let mut out = String::with_capacity(data.len() * 4 + 16);
+    for (index, line) in data.chunks(16).enumerate() {
+        out.push_str(&format!("{:08x} ", index * 16));
+        for column in 0..16 {
+            if column == 8 {
+                out.push(' ');
+            }
+            match line.get(column) {
+                Some(byte) => out.push_str(&format!(" {byte:02x}")),
+                None => out.push_str("   "),
+            }
+        }
+        out.push_str("  |");
+        for byte in line {
+            let c = if byte.is_ascii_graphic() || *byte == b' ' {
+                *byte as char
+            } else {
+                '.'
+            };
+            out.push(c);
+        }
+        out.push('|');
+        let line_marks = marks
+            .iter()
+            .filter(|(start, _)| (index * 16..index * 16 + 16).contains(start))
+            .map(|(start, name)| format!("{name}@0x{start:02x}"))
+            .collect::<Vec<String>>();
+        if !line_marks.is_empty() {
+            // 最后一行不满16字节时，补齐后再标记
+            out.push_str(&" ".repeat(16 - line.len()));
+            out.push_str("  # ");
+            out.push_str(&line_marks.join(" "));
+        }
+        out.push('\n');
+    }
+    out.push_str(&format!("{:08x}", data.len()));
+    out
+}
+
+// 协议层在输出数据中的开始位置，包括隧道内层，不标记链路层
+// pro_type: 整个报文的协议信息，start、len: 输出数据在报文中的位置
+pub(crate) fn layer_marks(pro_type: &ProType, start: usize, len: usize) -> Vec<(usize, String)> {
+    let mut marks = Vec::new();
+    for pro_type in pro_type.layers() {
+        let network = match pro_type.src_ip {
+            Some(std::net::IpAddr::V4(_)) => "IPv4",
+            Some(std::net::IpAddr::V6(_)) => "IPv6",
+            None => "网络层",
+        };
+        // 传输层负载：VXLAN、Geneve是隧道头，GRE头就是传输层，内层报文在下一层标记
+        // 没有隧道时是应用层，识别不出协议时也标记
+        let payload = match (pro_type.tunnel_pro, &pro_type.transport_pro) {
+            (Some(TunnelPro::GRE), _) => None,
+            (Some(tunnel_pro), _) => Some(format!("{tunnel_pro:?}")),
+            (None, TransportPro::TCP | TransportPro::UDP) => Some(match pro_type.application_pro {
+                ApplicationPro::Unsupported => "应用层".to_string(),
+                application_pro => format!("{application_pro:?}"),
+            }),
+            _ => None,
+        };
+        let payload_state = match payload {
+            Some(_)
+                if pro_type.transport_state == LayerState::Ok
+                    && pro_type.application_start < pro_type.packet_end =>
+            {
+                LayerState::Ok
+            }
+            _ => LayerState::Unsupported,
+        };
+        let layers = [
+            (
+                pro_type.network_state,
+                pro_type.network_start,
+                network.to_string(),
+            ),
+            (
+                pro_type.transport_state,
+                pro_type.transport_start,
+                format!("{:?}", pro_type.transport_pro),
+            ),
+            (
+                payload_state,
+                pro_type.application_start,
+                payload.unwrap_or_default(),
+            ),
+        ];
+        for (state, layer_start, name) in layers {
+            if state != LayerState::Unsupported && (start..start + len).contains(&layer_start) {
+                marks.push((layer_start - start, name));
+            }
+        }
+    }
+    marks.sort_by_key(|(start, _)| *start);
+    marks
+}
+
+// 标准base64，带填充
+pub(crate) fn base64(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = (bytes[0] as usize) << 16 | (bytes[1] as usize) << 8 | bytes[2] as usize;
+        for index in 0..4 {
+            if index <= chunk.len() {
+                out.push(BASE64_CHARS[n >> (18 - index * 6) & 0x3f] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 以太网 + IPv4 + TCP，或者目的端口是VXLAN的UDP
+    fn outer(protocol: u8, payload: &[u8]) -> Vec<u8> {
+        let transport_len = if protocol == 17 { 8 } else { 20 };
+        let total_len = (20 + transport_len + payload.len()) as u16;
+        let mut data = vec![0, 1, 2, 3, 4, 5, 0, 1, 2, 3, 4, 6, 0x08, 0x00];
+        data.extend_from_slice(&[0x45, 0, 0, 0, 0, 0, 0, 0, 64, protocol, 0, 0]);
+        data[16..18].copy_from_slice(&total_len.to_be_bytes());
+        data.extend_from_slice(&[10, 0, 0, 1, 10, 0, 0, 2]);
+        if protocol == 17 {
+            let udp_len = (8 + payload.len()) as u16;
+            data.extend_from_slice(&[0x13, 0x88, 0x12, 0xB5]);
+            data.extend_from_slice(&udp_len.to_be_bytes());
+            data.extend_from_slice(&[0, 0]);
+        } else {
+            data.extend_from_slice(&[0x13, 0x88, 0, 80, 0, 0, 0, 1, 0, 0, 0, 0]);
+            data.extend_from_slice(&[0x50, 0x18, 0x10, 0, 0, 0, 0, 0]);
+        }
+        data.extend_from_slice(payload);
+        data
+    }
+
+    fn analyze(data: &[u8]) -> ProType {
+        ProType::from_with_linktype(&pcap::Linktype::ETHERNET, data)
+    }
+
+    fn names(marks: &[(usize, String)]) -> Vec<(usize, &str)> {
+        marks
+            .iter()
+            .map(|(start, name)| (*start, name.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn application_marks() {
+        let data = outer(6, b"GET / HTTP/1.1\r\n\r\n");
+        let marks = layer_marks(&analyze(&data), 0, data.len());
+        assert_eq!(names(&marks), [(14, "IPv4"), (34, "TCP"), (54, "HTTP")]);
+        // 识别不出的负载也标记
+        let data = outer(6, b"\x16\x03\x01");
+        let marks = layer_marks(&analyze(&data), 0, data.len());
+        assert_eq!(names(&marks), [(14, "IPv4"), (34, "TCP"), (54, "应用层")]);
+        // 没有负载时不标记，输出从网络层开始时位置相对于输出数据
+        let data = outer(6, b"");
+        let marks = layer_marks(&analyze(&data), 14, data.len() - 14);
+        assert_eq!(names(&marks), [(0, "IPv4"), (20, "TCP")]);
+    }
+
+    #[test]
+    fn vxlan_marks() {
+        let inner = outer(6, b"data");
+        let mut vxlan = vec![0x08, 0, 0, 0, 0, 0, 1, 0];
+        vxlan.extend_from_slice(&inner);
+        let data = outer(17, &vxlan);
+        let marks = layer_marks(&analyze(&data), 0, data.len());
+        assert_eq!(
+            names(&marks),
+            [
+                (14, "IPv4"),
+                (34, "UDP"),
+                (42, "VXLAN"),
+                (64, "IPv4"),
+                (84, "TCP"),
+                (104, "应用层")
+            ]
+        );
+    }
+
+    #[test]
+    fn hexdump_with_marks() {
+        let data: Vec<u8> = (0x41..0x41 + 20).collect();
+        let marks = [(0, "IPv4".to_string()), (18, "TCP".to_string())];
+        assert_eq!(
+            hexdump_marked(&data, &marks),
+            "00000000  41 42 43 44 45 46 47 48  49 4a 4b 4c 4d 4e 4f 50  |ABCDEFGHIJKLMNOP|  # IPv4@0x00\n\
+             00000010  51 52 53 54                                       |QRST|              # TCP@0x12\n\
+             00000014"
+        );
+        assert_eq!(hexdump(&[]), "00000000");
+    }
+
+    #[test]
+    fn base64_padding() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foob"), "Zm9vYg==");
+        assert_eq!(base64(b"fooba"), "Zm9vYmE=");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+        assert_eq!(base64(&[0xFB, 0xFF, 0xBF]), "+/+/");
+    }
+}
@@ -1,9 +1,10 @@
-use crate::PacketInfo;
+use crate::analyze::ProType;
 
 use super::out_arg;
 
 // 获取基础数据
-pub(crate) fn get_data_fn(out_pro: &out_arg::OutPro) -> fn(&PacketInfo) -> &[u8] {
+// 有隧道时，pro_type可以是外层，也可以是内层，位置都是相对于整个报文的
+pub(crate) fn get_data_fn(out_pro: &out_arg::OutPro) -> for<'a> fn(&'a [u8], &ProType) -> &'a [u8] {
     match out_pro {
         out_arg::OutPro::Link => link_pro_data,
         out_arg::OutPro::Network => network_pro_data,
@@ -12,22 +13,37 @@ pub(crate) fn get_data_fn(out_pro: &out_arg::OutPro) -> fn(&PacketInfo) -> &[u8]
     }
 }
 
+// 输出数据在报文中的开始位置
+pub(crate) fn pro_start(out_pro: &out_arg::OutPro, pro_type: &ProType) -> usize {
+    match out_pro {
+        out_arg::OutPro::Link => pro_type.link_start,
+        out_arg::OutPro::Network => pro_type.network_start,
+        out_arg::OutPro::Transport => pro_type.transport_start,
+        out_arg::OutPro::Application => pro_type.application_start,
+    }
+}
+
+// 截取报文，越界时返回空数组
+fn slice_data(data: &[u8], start: usize, end: usize) -> &[u8] {
+    data.get(start..end).unwrap_or_default()
+}
+
 // 链路层报文
-fn link_pro_data(packet_info: &PacketInfo) -> &[u8] {
-    &packet_info.data[packet_info.pro_type.link_start..]
+fn link_pro_data<'a>(data: &'a [u8], pro_type: &ProType) -> &'a [u8] {
+    slice_data(data, pro_type.link_start, data.len())
 }
 
 // 网络层报文
-fn network_pro_data(packet_info: &PacketInfo) -> &[u8] {
-    &packet_info.data[packet_info.pro_type.network_start..]
+fn network_pro_data<'a>(data: &'a [u8], pro_type: &ProType) -> &'a [u8] {
+    slice_data(data, pro_type.network_start, pro_type.packet_end)
 }
 
 // 传输层报文
-fn transport_pro_data(packet_info: &PacketInfo) -> &[u8] {
-    &packet_info.data[packet_info.pro_type.transport_start..]
+fn transport_pro_data<'a>(data: &'a [u8], pro_type: &ProType) -> &'a [u8] {
+    slice_data(data, pro_type.transport_start, pro_type.packet_end)
 }
 
 // 应用层报文
-fn application_pro_data(packet_info: &PacketInfo) -> &[u8] {
-    &packet_info.data[packet_info.pro_type.application_start..]
+fn application_pro_data<'a>(data: &'a [u8], pro_type: &ProType) -> &'a [u8] {
+    slice_data(data, pro_type.application_start, pro_type.packet_end)
 }
@@ -1,28 +1,56 @@
-use std::{borrow::Cow, fmt::Debug};
+use std::{borrow::Cow, fmt::Debug, time::Duration};
+
+use crate::{
+    analyze::{ApplicationPro, ProType},
+    process::Redact,
+    stream::{CloseReason, StreamKey},
+};
 
 // 参数，输出相关
 #[derive(Debug)]
 pub struct OutArg {
     // 输出形式
     pub out_type: OutType,
+    // hexdump输出时，标记各协议层的开始位置，只在逐个报文输出时生效
+    pub hexdump_layers: bool,
     // 输出数据层
     pub out_pro: OutPro,
+    // 有隧道时，输出数据层对应外层还是内层
+    pub out_tunnel: OutTunnel,
+    // 抓包时间显示形式，没有值时不显示
+    pub out_time: Option<OutTime>,
+    // 抓包时间精度
+    pub time_precision: TimePrecision,
+    // 每条输出前的说明行（地址、协议、标志位、长度、时间），没有值时不显示
+    pub out_head: Option<OutHead>,
+    // 按TCP流重组后处理的应用层协议，没有值时逐个报文处理
+    // 只有输出应用层时才会重组
+    pub stream_pro: Option<ApplicationPro>,
     // 协议控制
     pub pro_arg: Box<dyn ProArg>,
     // 文件名，有值时，输出到文件，没有值时，输出到控制台
     pub out_file: Option<String>,
     // pcap文件名，和tcpdump -w命令相同
     pub pcap_file_name: Option<String>,
+    // 写pcap文件时的脱敏，没有值时原样写入
+    pub pcap_redact: Option<Redact>,
 }
 
 impl OutArg {
     pub fn new() -> OutArg {
         OutArg {
             out_type: OutType::Itself,
+            hexdump_layers: false,
             out_pro: OutPro::Application,
+            out_tunnel: OutTunnel::Inner,
+            out_time: None,
+            time_precision: TimePrecision::Micro,
+            out_head: None,
+            stream_pro: None,
             pro_arg: Box::new(ProArgNone),
             out_file: None,
             pcap_file_name: None,
+            pcap_redact: None,
         }
     }
 
@@ -30,10 +58,17 @@ impl OutArg {
     pub fn new_with_pro_arg(pro_arg: Box<dyn ProArg>) -> OutArg {
         OutArg {
             out_type: OutType::Itself,
+            hexdump_layers: false,
             out_pro: OutPro::Application,
+            out_tunnel: OutTunnel::Inner,
+            out_time: None,
+            time_precision: TimePrecision::Micro,
+            out_head: None,
+            stream_pro: None,
             pro_arg,
             out_file: None,
             pcap_file_name: None,
+            pcap_redact: None,
         }
     }
 }
@@ -47,6 +82,10 @@ pub enum OutType {
     Decimal,
     // 16进制
     Hexadecimal,
+    // 和hexdump -C相同，带偏移量和ASCII
+    Hexdump,
+    // JSON Lines，每个报文或者http消息一行json
+    Json,
 }
 
 impl OutType {
@@ -55,6 +94,8 @@ impl OutType {
             "itself" => Some(OutType::Itself),
             "decimal" => Some(OutType::Decimal),
             "hexadecimal" => Some(OutType::Hexadecimal),
+            "hexdump" => Some(OutType::Hexdump),
+            "json" => Some(OutType::Json),
             _ => None,
         }
     }
@@ -81,10 +122,116 @@ impl OutPro {
     }
 }
 
+// 有隧道时，输出哪一层报文
+#[derive(Debug)]
+pub enum OutTunnel {
+    // 最外层，就是捕获到的报文
+    Outer,
+    // 最内层
+    Inner,
+}
+
+impl OutTunnel {
+    pub fn from_name(name: &str) -> Option<OutTunnel> {
+        match name {
+            "outer" => Some(OutTunnel::Outer),
+            "inner" => Some(OutTunnel::Inner),
+            _ => None,
+        }
+    }
+
+    // 选择输出的协议信息
+    pub(crate) fn select<'a>(&self, pro_type: &'a ProType) -> &'a ProType {
+        match self {
+            OutTunnel::Outer => pro_type,
+            OutTunnel::Inner => pro_type.innermost(),
+        }
+    }
+}
+
+// 抓包时间显示形式
+#[derive(Debug, Clone, Copy)]
+pub enum OutTime {
+    // 绝对时间，本地时区
+    Absolute,
+    // 相对第一个报文的时间
+    Relative,
+    // 相对上一次输出的时间
+    Delta,
+}
+
+impl OutTime {
+    pub fn from_name(name: &str) -> Option<OutTime> {
+        match name {
+            "absolute" => Some(OutTime::Absolute),
+            "relative" => Some(OutTime::Relative),
+            "delta" => Some(OutTime::Delta),
+            _ => None,
+        }
+    }
+}
+
+// 抓包时间精度
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimePrecision {
+    // 微秒
+    Micro,
+    // 纳秒，抓包时也会按纳秒精度获取时间
+    Nano,
+}
+
+impl TimePrecision {
+    pub fn from_name(name: &str) -> Option<TimePrecision> {
+        match name {
+            "us" => Some(TimePrecision::Micro),
+            "ns" => Some(TimePrecision::Nano),
+            _ => None,
+        }
+    }
+}
+
+// 说明行形式
+#[derive(Debug, Clone, Copy)]
+pub enum OutHead {
+    // 简洁，一行，和tcpdump类似
+    Compact,
+    // 详细，带协议栈、序列号、抓包长度、VLAN、隧道
+    Verbose,
+}
+
+impl OutHead {
+    pub fn from_name(name: &str) -> Option<OutHead> {
+        match name {
+            "compact" => Some(OutHead::Compact),
+            "verbose" => Some(OutHead::Verbose),
+            _ => None,
+        }
+    }
+}
+
 pub trait ProArg: Debug {
     // 字节处理
     // 会在类型转换前调用
     fn byte_process<'a>(&self, data: &'a [u8]) -> Cow<'a, [u8]>;
+
+    // TCP流数据处理，TCP流重组后调用
+    // data是某个方向上按序到达的数据，ts是到达时的抓包时间
+    // 返回需要输出的数据，每一项单独输出，默认直接按字节处理
+    fn stream_process(&mut self, _key: &StreamKey, _ts: Duration, data: &[u8]) -> Vec<Vec<u8>> {
+        vec![self.byte_process(data).into_owned()]
+    }
+
+    // TCP连接结束，返回还没有输出的数据
+    // key是连接上第一个发送数据的方向
+    fn stream_close(&mut self, _key: &StreamKey, _reason: CloseReason) -> Vec<Vec<u8>> {
+        Vec::new()
+    }
+
+    // 切换到json输出，返回是否支持
+    // 支持时，stream_process、stream_close返回的每一项是一个json对象，否则由调用方包装
+    fn json(&mut self) -> bool {
+        false
+    }
 }
 
 // 无协议控制
@@ -0,0 +1,140 @@
+use std::net::{IpAddr, SocketAddr};
+
+use crate::{
+    analyze::{
+        ApplicationPro, ProType, TransportPro, TCP_ACK, TCP_FIN, TCP_PSH, TCP_RST, TCP_SYN, TCP_URG,
+    },
+    stream::StreamKey,
+    PacketInfo,
+};
+
+use super::OutHead;
+
+// 逐个报文输出时的说明行
+// pro_type: 输出层对应的协议信息，有隧道时可能是内层
+// time: 格式化后的抓包时间，data_len: 输出数据的长度
+pub(super) fn packet_head(
+    out_head: OutHead,
+    packet_info: &PacketInfo,
+    pro_type: &ProType,
+    time: Option<String>,
+    data_len: usize,
+) -> String {
+    let src = address(pro_type.src_ip, pro_type.src_port, &pro_type.transport_pro);
+    let dst = address(pro_type.dst_ip, pro_type.dst_port, &pro_type.transport_pro);
+    let mut head = time.map(|time| time + " ").unwrap_or_default();
+    match out_head {
+        OutHead::Compact => {
+            head.push_str(&format!("{src} > {dst} {:?}", pro_type.transport_pro));
+            if let TransportPro::TCP = pro_type.transport_pro {
+                head.push(' ');
+                head.push_str(&tcp_flags(pro_type.tcp_flags));
+            }
+            head.push_str(&format!(" len {data_len}"));
+        }
+        OutHead::Verbose => {
+            head.push_str(&format!("{src} -> {dst}  协议: "));
+            let network = match pro_type.src_ip {
+                Some(IpAddr::V4(_)) => "IPv4",
+                Some(IpAddr::V6(_)) => "IPv6",
+                None => "?",
+            };
+            head.push_str(network);
+            head.push_str(&format!("/{:?}", pro_type.transport_pro));
+            if pro_type.application_pro != ApplicationPro::Unsupported {
+                head.push_str(&format!("/{:?}", pro_type.application_pro));
+            }
+            if let TransportPro::TCP = pro_type.transport_pro {
+                head.push_str(&format!(
+                    "  标志: {}  seq: {}",
+                    tcp_flags(pro_type.tcp_flags),
+                    pro_type.tcp_seq
+                ));
+            }
+            head.push_str(&format!(
+                "  长度: {data_len}  抓包长度: {}  原始长度: {}",
+                packet_info.caplen, packet_info.len
+            ));
+            // 方向、网口、VLAN和隧道记录在外层
+            if let Some(direction) = packet_info.pro_type.direction {
+                head.push_str(&format!("  方向: {}", direction.name()));
+            }
+            if let Some(if_index) = packet_info.pro_type.if_index {
+                head.push_str(&format!("  网口: {if_index}"));
+            }
+            let vlan_ids = packet_info
+                .pro_type
+                .layers()
+                .flat_map(|pro_type| pro_type.vlan_ids.iter())
+                .map(|vlan_id| vlan_id.to_string())
+                .collect::<Vec<String>>();
+            if !vlan_ids.is_empty() {
+                head.push_str(&format!("  VLAN: {}", vlan_ids.join(",")));
+            }
+            let tunnels = packet_info
+                .pro_type
+                .layers()
+                .filter_map(|pro_type| pro_type.tunnel_pro)
+                .map(|tunnel_pro| format!("{tunnel_pro:?}"))
+                .collect::<Vec<String>>();
+            if !tunnels.is_empty() {
+                head.push_str(&format!("  隧道: {}", tunnels.join(",")));
+            }
+        }
+    }
+    head
+}
+
+// TCP流重组后输出时的说明行
+// key: 触发输出的数据方向
+pub(super) fn stream_head(
+    out_head: OutHead,
+    key: &StreamKey,
+    application_pro: ApplicationPro,
+    time: Option<String>,
+    data_len: usize,
+) -> String {
+    let mut head = time.map(|time| time + " ").unwrap_or_default();
+    match out_head {
+        OutHead::Compact => head.push_str(&format!(
+            "{} > {} TCP {application_pro:?} len {data_len}",
+            key.src, key.dst
+        )),
+        OutHead::Verbose => head.push_str(&format!(
+            "{} -> {}  协议: TCP/{application_pro:?}  TCP流重组  长度: {data_len}",
+            key.src, key.dst
+        )),
+    }
+    head
+}
+
+// 地址，TCP、UDP时带端口
+pub(super) fn address(ip: Option<IpAddr>, port: u16, transport_pro: &TransportPro) -> String {
+    match (ip, transport_pro) {
+        (None, _) => "?".to_string(),
+        (Some(ip), TransportPro::TCP | TransportPro::UDP) => SocketAddr::new(ip, port).to_string(),
+        (Some(ip), _) => ip.to_string(),
+    }
+}
+
+// TCP标志位，和tcpdump相同，ACK显示为.
+pub(super) fn tcp_flags(flags: u8) -> String {
+    let names = [
+        (TCP_FIN, 'F'),
+        (TCP_SYN, 'S'),
+        (TCP_RST, 'R'),
+        (TCP_PSH, 'P'),
+        (TCP_URG, 'U'),
+        (TCP_ACK, '.'),
+    ];
+    let text = names
+        .iter()
+        .filter(|(flag, _)| flags & flag != 0)
+        .map(|(_, name)| *name)
+        .collect::<String>();
+    if text.is_empty() {
+        "[none]".to_string()
+    } else {
+        format!("[{text}]")
+    }
+}
@@ -0,0 +1,234 @@
+use std::{net::IpAddr, time::Duration};
+
+use serde_json::{json, Value};
+
+use crate::{
+    analyze::{ApplicationPro, LayerState, ProType, TransportPro},
+    stream::StreamKey,
+    PacketInfo,
+};
+
+use super::{change_data, out_head};
+
+// 逐个报文输出时的json对象
+// pro_type: 输出层对应的协议信息，有隧道时可能是内层
+// time: 格式化后的抓包时间，data: 协议控制后的输出数据
+pub(super) fn packet_json(
+    packet_info: &PacketInfo,
+    pro_type: &ProType,
+    time: Option<String>,
+    data: &[u8],
+) -> String {
+    let mut json = json!({
+        "ts": packet_info.ts.as_secs_f64(),
+        "src": out_head::address(pro_type.src_ip, pro_type.src_port, &pro_type.transport_pro),
+        "dst": out_head::address(pro_type.dst_ip, pro_type.dst_port, &pro_type.transport_pro),
+        "network": match pro_type.src_ip {
+            Some(IpAddr::V4(_)) => Some("IPv4"),
+            Some(IpAddr::V6(_)) => Some("IPv6"),
+            None => None,
+        },
+        "transport": format!("{:?}", pro_type.transport_pro),
+        "caplen": packet_info.caplen,
+        "len": packet_info.len,
+    });
+    if let Some(time) = time {
+        json["time"] = json!(time);
+    }
+    if pro_type.application_pro != ApplicationPro::Unsupported {
+        json["application"] = json!(format!("{:?}", pro_type.application_pro));
+    }
+    if let TransportPro::TCP = pro_type.transport_pro {
+        json["tcp_flags"] = json!(out_head::tcp_flags(pro_type.tcp_flags));
+        json["tcp_seq"] = json!(pro_type.tcp_seq);
+    }
+    // 各层在整个报文中的开始位置，没有解析的层为null
+    json["offsets"] = json!({
+        "link": offset(pro_type.link_state, pro_type.link_start),
+        "network": offset(pro_type.network_state, pro_type.network_start),
+        "transport": offset(pro_type.transport_state, pro_type.transport_start),
+        "application": offset(pro_type.application_state, pro_type.application_start),
+        "end": pro_type.packet_end,
+    });
+    // 方向、网口、VLAN和隧道记录在外层
+    if let Some(direction) = packet_info.pro_type.direction {
+        json["direction"] = json!(direction.name());
+    }
+    if let Some(if_index) = packet_info.pro_type.if_index {
+        json["if_index"] = json!(if_index);
+    }
+    let vlan_ids: Vec<u16> = packet_info
+        .pro_type
+        .layers()
+        .flat_map(|pro_type| pro_type.vlan_ids.iter().copied())
+        .collect();
+    if !vlan_ids.is_empty() {
+        json["vlan"] = json!(vlan_ids);
+    }
+    let tunnels: Vec<String> = packet_info
+        .pro_type
+        .layers()
+        .filter_map(|pro_type| pro_type.tunnel_pro)
+        .map(|tunnel_pro| format!("{tunnel_pro:?}"))
+        .collect();
+    if !tunnels.is_empty() {
+        json["tunnel"] = json!(tunnels);
+    }
+    data_json(&mut json, data);
+    json.to_string()
+}
+
+// TCP流重组后输出时的json对象，协议控制不支持json时使用
+// key: 触发输出的数据方向
+pub(super) fn stream_json(
+    key: &StreamKey,
+    application_pro: Option<ApplicationPro>,
+    ts: Duration,
+    time: Option<String>,
+    data: &[u8],
+) -> String {
+    let mut json = json!({
+        "ts": ts.as_secs_f64(),
+        "src": key.src.to_string(),
+        "dst": key.dst.to_string(),
+        "transport": "TCP",
+    });
+    if let Some(time) = time {
+        json["time"] = json!(time);
+    }
+    if let Some(application_pro) = application_pro {
+        json["application"] = json!(format!("{application_pro:?}"));
+    }
+    data_json(&mut json, data);
+    json.to_string()
+}
+
+// 解析过的层才有位置
+fn offset(state: LayerState, start: usize) -> Option<usize> {
+    match state {
+        LayerState::Unsupported => None,
+        _ => Some(start),
+    }
+}
+
+// 输出数据，UTF-8文本原样输出，否则用base64编码
+fn data_json(json: &mut Value, data: &[u8]) {
+    json["data_len"] = json!(data.len());
+    match std::str::from_utf8(data) {
+        Ok(text) => json["data"] = json!(text),
+        Err(_) => {
+            json["data"] = json!(change_data::base64(data));
+            json["data_encoding"] = json!("base64");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 以太网 + IPv4 + TCP，5000 -> 80
+    fn packet(payload: &[u8]) -> PacketInfo {
+        let total_len = (40 + payload.len()) as u16;
+        let mut data = vec![0, 1, 2, 3, 4, 5, 0, 1, 2, 3, 4, 6, 0x08, 0x00];
+        data.extend_from_slice(&[0x45, 0, 0, 0, 0, 0, 0, 0, 64, 6, 0, 0]);
+        data[16..18].copy_from_slice(&total_len.to_be_bytes());
+        data.extend_from_slice(&[10, 0, 0, 1, 10, 0, 0, 2]);
+        data.extend_from_slice(&[0x13, 0x88, 0, 80, 0, 0, 0, 1, 0, 0, 0, 0]);
+        data.extend_from_slice(&[0x50, 0x18, 0x10, 0, 0, 0, 0, 0]);
+        data.extend_from_slice(payload);
+        PacketInfo {
+            pro_type: ProType::from_with_linktype(&pcap::Linktype::ETHERNET, &data),
+            ts: Duration::from_millis(1500),
+            caplen: data.len() as u32,
+            len: data.len() as u32,
+            data,
+        }
+    }
+
+    // 一条记录一行，能解析成json对象
+    fn parse(line: &str) -> Value {
+        assert!(!line.contains('\n'));
+        let json: Value = serde_json::from_str(line).unwrap();
+        assert!(json.is_object());
+        json
+    }
+
+    #[test]
+    fn text_packet() {
+        let payload = b"GET / HTTP/1.1\r\nHost: a\r\n\r\n";
+        let packet_info = packet(payload);
+        let line = packet_json(
+            &packet_info,
+            &packet_info.pro_type,
+            Some("time".to_string()),
+            payload,
+        );
+        let json = parse(&line);
+        assert_eq!(json["ts"], 1.5);
+        assert_eq!(json["time"], "time");
+        assert!(json["src"].as_str().unwrap().contains("10.0.0.1"));
+        assert!(json["dst"].as_str().unwrap().contains("10.0.0.2"));
+        assert_eq!(json["network"], "IPv4");
+        assert_eq!(json["transport"], "TCP");
+        assert_eq!(json["tcp_seq"], 1);
+        assert_eq!(json["caplen"], packet_info.caplen);
+        assert_eq!(json["len"], packet_info.len);
+        assert_eq!(
+            json["offsets"],
+            json!({"link": 0, "network": 14, "transport": 34, "application": 54, "end": 54 + payload.len()})
+        );
+        assert_eq!(json["data"], std::str::from_utf8(payload).unwrap());
+        assert_eq!(json["data_len"], payload.len());
+        assert!(json.get("data_encoding").is_none());
+        assert!(json.get("vlan").is_none() && json.get("tunnel").is_none());
+    }
+
+    #[test]
+    fn binary_packet() {
+        let payload = [0xff, 0x00, b'\n', 0xfe];
+        let packet_info = packet(&payload);
+        let json = parse(&packet_json(
+            &packet_info,
+            &packet_info.pro_type,
+            None,
+            &payload,
+        ));
+        assert!(json.get("time").is_none());
+        assert_eq!(json["data"], change_data::base64(&payload));
+        assert_eq!(json["data_encoding"], "base64");
+        assert_eq!(json["data_len"], payload.len());
+    }
+
+    #[test]
+    fn stream() {
+        let key = StreamKey {
+            src: ([10, 0, 0, 1], 5000).into(),
+            dst: ([10, 0, 0, 2], 80).into(),
+        };
+        let json = parse(&stream_json(
+            &key,
+            Some(ApplicationPro::HTTP),
+            Duration::from_secs(2),
+            None,
+            b"line1\nline2",
+        ));
+        assert_eq!(json["src"], "10.0.0.1:5000");
+        assert_eq!(json["dst"], "10.0.0.2:80");
+        assert_eq!(json["transport"], "TCP");
+        assert_eq!(json["application"], "HTTP");
+        assert_eq!(json["ts"], 2.0);
+        assert_eq!(json["data"], "line1\nline2");
+
+        let json = parse(&stream_json(
+            &key,
+            None,
+            Duration::ZERO,
+            None,
+            &[0x80, 0x81],
+        ));
+        assert!(json.get("application").is_none());
+        assert_eq!(json["data"], change_data::base64(&[0x80, 0x81]));
+        assert_eq!(json["data_encoding"], "base64");
+    }
+}
@@ -0,0 +1,63 @@
+use std::time::Duration;
+
+use super::{pro_data::local_time, OutArg, OutTime, TimePrecision};
+
+// 抓包时间显示
+pub(super) struct TimeShow {
+    out_time: Option<OutTime>,
+    precision: TimePrecision,
+    // 第一个报文的时间
+    first: Option<Duration>,
+    // 上一次输出的时间
+    prev: Option<Duration>,
+}
+
+impl TimeShow {
+    pub fn new(out_arg: &OutArg) -> Self {
+        // 显示说明行时，默认显示绝对时间
+        let out_time = out_arg
+            .out_time
+            .or(out_arg.out_head.map(|_| OutTime::Absolute));
+        TimeShow {
+            out_time,
+            precision: out_arg.time_precision,
+            first: None,
+            prev: None,
+        }
+    }
+
+    // 记录收到的报文时间，相对时间从第一个报文开始计算
+    pub fn receive(&mut self, ts: Duration) {
+        if self.first.is_none() {
+            self.first = Some(ts);
+        }
+    }
+
+    // 格式化一次输出的时间，不显示时间时返回None
+    pub fn show(&mut self, ts: Duration) -> Option<String> {
+        let text = match self.out_time? {
+            OutTime::Absolute => self.absolute(ts),
+            OutTime::Relative => self.seconds(ts.saturating_sub(self.first.unwrap_or(ts))),
+            OutTime::Delta => self.seconds(ts.saturating_sub(self.prev.unwrap_or(ts))),
+        };
+        self.prev = Some(ts);
+        Some(text)
+    }
+
+    // 本地时区的日期时间
+    fn absolute(&self, ts: Duration) -> String {
+        let time = local_time(ts);
+        match self.precision {
+            TimePrecision::Micro => time.format("%Y-%m-%d %H:%M:%S%.6f").to_string(),
+            TimePrecision::Nano => time.format("%Y-%m-%d %H:%M:%S%.9f").to_string(),
+        }
+    }
+
+    // 秒数，带小数
+    fn seconds(&self, ts: Duration) -> String {
+        match self.precision {
+            TimePrecision::Micro => format!("{}.{:06}", ts.as_secs(), ts.subsec_micros()),
+            TimePrecision::Nano => format!("{}.{:09}", ts.as_secs(), ts.subsec_nanos()),
+        }
+    }
+}
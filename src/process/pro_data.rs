@@ -1,3 +1,6 @@
 mod pro_http;
 
-pub use pro_http::ProArgHttp;
+pub use pro_http::{
+    charset_from_label, local_time, GrepTarget, HarWriter, HeaderFilter, HttpFilter, ObjectExport,
+    ProArgHttp, Redact, Replay,
+};
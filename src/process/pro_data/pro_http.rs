@@ -1,8 +1,61 @@
-use std::{borrow::Cow, collections::HashMap, io::Read as _};
+use std::{
+    borrow::Cow,
+    collections::{HashMap, VecDeque},
+    io::Read,
+    time::Duration,
+};
 
-use flate2::read::GzDecoder;
+use flate2::read::{DeflateDecoder, MultiGzDecoder, ZlibDecoder};
+use regex::Regex;
 
-use crate::process::out_arg::ProArg;
+use crate::{
+    process::out_arg::ProArg,
+    stream::{CloseReason, StreamKey},
+};
+
+use har::HarContent;
+use message::{DecodedBody, HttpHeaders, HttpMessage, MessageParser};
+use replay::ReplayResult;
+use transaction::HttpTransaction;
+
+pub use charset::from_label as charset_from_label;
+pub use filter::{GrepTarget, HeaderFilter, HttpFilter};
+pub use har::{local_time, HarWriter};
+pub use objects::ObjectExport;
+pub use redact::Redact;
+pub use replay::Replay;
+
+// 二进制消息体
+mod binary;
+// 字符集转换
+mod charset;
+// 分块传输解码
+mod chunked;
+// 生成curl命令
+mod curl;
+// http过滤
+mod filter;
+// HAR文件导出
+mod har;
+// http消息解析
+mod message;
+// json输出
+mod json;
+// 消息体导出到目录
+mod objects;
+// 消息体格式化
+mod pretty;
+// 敏感信息脱敏
+mod redact;
+// 请求回放
+mod replay;
+// 请求和响应配对
+mod transaction;
+
+// 解压后的最大长度，防止解压炸弹
+const MAX_DECOMPRESS_LEN: usize = 32 * 1024 * 1024;
+// 猜测编码、判断二进制时，最多检查消息体开头的长度
+const SNIFF_LEN: usize = 1024;
 
 // http协议控制
 #[derive(Debug)]
@@ -13,6 +66,84 @@ pub struct ProArgHttp {
     pub body_show: bool,
     // 是否输出原值
     pub itself: bool,
+    // 强制使用的字符集，没有时使用Content-Type中的字符集，都没有时猜测
+    pub charset: Option<&'static encoding_rs::Encoding>,
+    // 按Content-Type格式化消息体，输出原值时不生效
+    pub pretty: bool,
+    // 二进制消息体显示hexdump，否则显示摘要
+    pub hexdump: bool,
+    // 解析消息后过滤
+    pub filter: HttpFilter,
+    // 敏感信息脱敏，没有值时不脱敏
+    pub redact: Option<Redact>,
+    // 按TCP流解析时，请求和响应写入HAR文件
+    pub har: Option<HarWriter>,
+    // 按TCP流解析时，解码后的消息体导出到目录
+    pub export: Option<ObjectExport>,
+    // 导出消息体时，也导出请求体
+    pub export_request: bool,
+    // 请求输出为curl命令，不输出响应
+    pub curl: bool,
+    // 请求回放到指定的服务端，输出回放结果
+    pub replay: Option<Replay>,
+    // 按TCP流解析时，每条消息输出一个json对象，由输出类型决定
+    json: bool,
+    // 按TCP流解析的连接，key是连接上第一个发送数据的方向
+    connections: HashMap<StreamKey, HttpConnection>,
+}
+
+// http连接，两个方向分别解析
+#[derive(Debug)]
+struct HttpConnection {
+    // 和key同向
+    forward: MessageParser,
+    // 和key反向
+    backward: MessageParser,
+    // 还没有收到响应的请求方法
+    methods: VecDeque<String>,
+    // 还没有收到响应的请求和回放结果，支持管道化，按顺序和响应配对
+    requests: VecDeque<(StreamKey, HttpMessage, Option<ReplayResult>)>,
+}
+
+impl HttpConnection {
+    fn new() -> Self {
+        HttpConnection {
+            forward: MessageParser::new(),
+            backward: MessageParser::new(),
+            methods: VecDeque::new(),
+            requests: VecDeque::new(),
+        }
+    }
+
+    // 请求和响应配对，key是消息的方向，messages中请求带着回放结果
+    // 请求先缓存，收到最终响应时，和最早的请求配对
+    fn pair(
+        &mut self,
+        key: StreamKey,
+        messages: Vec<(HttpMessage, Option<ReplayResult>)>,
+    ) -> Vec<HttpTransaction> {
+        let mut transactions = Vec::new();
+        for (message, replayed) in messages {
+            match message.status() {
+                None => self.requests.push_back((key, message, replayed)),
+                // 1xx是临时响应，不参与配对
+                Some(status) if (100..200).contains(&status) && status != 101 => {}
+                Some(_) => {
+                    let (request, replayed) = match self.requests.pop_front() {
+                        Some((_, request, replayed)) => (Some(request), replayed),
+                        None => (None, None),
+                    };
+                    transactions.push(HttpTransaction {
+                        key: key.reverse(),
+                        request,
+                        response: Some(message),
+                        replayed,
+                    });
+                }
+            }
+        }
+        transactions
+    }
 }
 
 impl ProArgHttp {
@@ -21,96 +152,123 @@ impl ProArgHttp {
             head_show: true,
             body_show: true,
             itself: false,
+            charset: None,
+            pretty: false,
+            hexdump: false,
+            filter: HttpFilter::default(),
+            redact: None,
+            har: None,
+            export: None,
+            export_request: false,
+            curl: false,
+            replay: None,
+            json: false,
+            connections: HashMap::new(),
         }
     }
 
-    // 寻找\r\n\r\n
-    fn find_sep_index(data: &[u8]) -> usize {
-        let index = data
-            .windows(4)
-            .enumerate()
-            .find(|(_, window)| window == b"\r\n\r\n");
-        if let Some(index) = index {
-            index.0
-        } else {
-            // 未找到http的请求头和请求体，认为都是请求头
-            data.len()
-        }
-    }
-
-    // 转文本
-    // content_type: http请求头，Content-Type: text/html
-    // content_encoding: http请求头，Content-Encoding: gzip
-    fn u8_to_str<'a>(
-        &self,
-        content_type: Option<&str>,
-        data: &'a [u8],
-    ) -> Cow<'a, str> {
-        // 尝试从Content-Type中，获取编码
-        let _encoding = content_type
-            .map(|v| {
-                v.rsplit(";")
-                    .find_map(|s| s.trim().strip_prefix("charset="))
-            })
-            // 目前，只考虑支持UTF8编码
-            .flatten()
-            .unwrap_or("UTF8");
-
-        String::from_utf8_lossy(data)
+    // 转文本，按字符集转成UTF-8
+    // content_type: http请求头，Content-Type: text/html; charset=GBK
+    fn u8_to_str<'a>(&self, content_type: Option<&str>, data: &'a [u8]) -> Cow<'a, str> {
+        charset::decode(self.charset, content_type, data)
     }
 
     // 分块传输合并
-    fn combin_data<'a>(head_map: &HashMap<String, String>, data: Cow<'a, [u8]>) -> Cow<'a, [u8]> {
-        let transfer_encoding = head_map.get("Transfer-Encoding");
-        match transfer_encoding {
-            None => return data,
-            Some(x) if x == "chunked" => return Self::combin_chunked(data),
-            // 不支持的分块传输协议
-            _ => return data,
-        }
-    }
-
-    // Transfer-Encoding:chunked 合并请求体
-    fn combin_chunked<'a>(data: Cow<'a, [u8]>) -> Cow<'a, [u8]> {
-        // 目前看，拿到的报文不全，
-        // 尝试拿到内容
-        let index = data
-            .windows(2) // 使用 windows(2) 创建一个包含相邻两个字节的迭代器
-            .position(|window| window == &[0x0D, 0x0A]); // 找到第一个匹配 &[0x0D, 0x0A] 的窗口
-            // .map(|i| &bytes[i + 2..]); // 如果找到匹配，返回剩余的字节数组
-        match index {
-            None => Cow::from(&[] as &[u8]),
-            Some(i) => {
-                match data {
-                    Cow::Owned(mut d) => Cow::from(d.split_off(i + 2)),
-                    Cow::Borrowed(d) => Cow::Borrowed(&d[i + 2..]),
+    // 返回合并后的消息体、尾部字段，以及分块传输是否不完整
+    fn combin_data<'a>(
+        message: &HttpMessage,
+        data: Cow<'a, [u8]>,
+    ) -> (Cow<'a, [u8]>, Vec<u8>, bool) {
+        if message.is_chunked() {
+            let chunked_body = chunked::decode(&data);
+            return (
+                Cow::Owned(chunked_body.data),
+                chunked_body.trailer,
+                chunked_body.truncated,
+            );
+        }
+        // 没有分块传输，或者不支持的分块传输协议
+        (data, Vec::new(), false)
+    }
+
+    // 解压
+    // 多个编码时，按照相反的顺序解码，比如 Content-Encoding: gzip, br 先解br再解gzip
+    // 传输编码在内容编码的外层，chunked已经处理过
+    // notes: 解压过程中的说明，输出在消息后面
+    fn decompress<'a>(
+        headers: &HttpHeaders,
+        data: Cow<'a, [u8]>,
+        notes: &mut Vec<String>,
+    ) -> Cow<'a, [u8]> {
+        let transfer_encodings = headers
+            .get_list("Transfer-Encoding")
+            .filter(|x| !x.eq_ignore_ascii_case("chunked"));
+        let encodings: Vec<&str> = headers
+            .get_list("Content-Encoding")
+            .chain(transfer_encodings)
+            .collect();
+        let mut data = data;
+        for encoding in encodings.iter().rev() {
+            let decoder: Box<dyn Read + '_> = match encoding.to_ascii_lowercase().as_str() {
+                "identity" => continue,
+                "gzip" | "x-gzip" => Box::new(MultiGzDecoder::new(data.as_ref())),
+                // 标准是zlib格式，有些服务端直接发送deflate原始数据
+                "deflate" if Self::is_zlib(&data) => Box::new(ZlibDecoder::new(data.as_ref())),
+                "deflate" => Box::new(DeflateDecoder::new(data.as_ref())),
+                "br" => Box::new(brotli_decompressor::Decompressor::new(data.as_ref(), 4096)),
+                "zstd" => match zstd::stream::read::Decoder::new(data.as_ref()) {
+                    Ok(decoder) => Box::new(decoder),
+                    Err(error) => {
+                        notes.push(format!("{encoding}解压失败: {error}"));
+                        return data;
+                    }
+                },
+                _ => {
+                    notes.push(format!("不支持的压缩协议: {encoding}"));
+                    return data;
                 }
+            };
+            match Self::read_limit(encoding, decoder, notes) {
+                Some(decompress_data) => data = Cow::Owned(decompress_data),
+                // 解压失败，之后的编码也无法处理，输出当前的数据
+                None => return data,
             }
         }
+        data
     }
 
-    // 解压
-    fn decompress<'a>(head_map: &HashMap<String, String>, data: Cow<'a, [u8]>) -> Cow<'a, [u8]> {
-        let content_encoding = head_map.get("Content-Encoding");
-        match content_encoding {
-            None => return data,
-            Some(x) if x == "identity" => return data,
-            Some(x) if x == "gzip" => return Self::decompress_gzip(data),
-            _ => return Cow::from("不支持的压缩协议".as_bytes()),
+    // zlib头: 压缩方法是8，头两个字节按大端是31的倍数
+    fn is_zlib(data: &[u8]) -> bool {
+        match data {
+            [cmf, flg, ..] => cmf & 0x0F == 8 && (u16::from(*cmf) << 8 | u16::from(*flg)) % 31 == 0,
+            _ => false,
         }
     }
 
-    // gzip压缩协议解压
-    fn decompress_gzip<'a>(data: Cow<'a, [u8]>) -> Cow<'a, [u8]> {
-        dbg!(data.len());
-        let mut decoder = GzDecoder::new(data.as_ref());
-        let mut decompresse_data = Vec::new();
-        let decompress_result = decoder.read_to_end(&mut decompresse_data);
-        dbg!(&decompress_result); 
-        match decompress_result {
-            Ok(_) => Cow::Owned(decompresse_data),
-            Err(_) => data,
+    // 读取解压后的数据，最多读取MAX_DECOMPRESS_LEN
+    // 数据不完整时，返回已经解压的部分，一点都没有解压出来时返回None
+    fn read_limit(
+        encoding: &str,
+        decoder: Box<dyn Read + '_>,
+        notes: &mut Vec<String>,
+    ) -> Option<Vec<u8>> {
+        let mut decompress_data = Vec::new();
+        let result = decoder
+            .take(MAX_DECOMPRESS_LEN as u64 + 1)
+            .read_to_end(&mut decompress_data);
+        match result {
+            Ok(_) if decompress_data.len() > MAX_DECOMPRESS_LEN => {
+                decompress_data.truncate(MAX_DECOMPRESS_LEN);
+                notes.push(format!("解压后超过{MAX_DECOMPRESS_LEN}字节，已截断"));
+            }
+            Ok(_) => {}
+            Err(error) if decompress_data.is_empty() => {
+                notes.push(format!("{encoding}解压失败: {error}"));
+                return None;
+            }
+            Err(error) => notes.push(format!("{encoding}解压不完整: {error}")),
         }
+        Some(decompress_data)
     }
 
     // 分析显示内容
@@ -126,62 +284,714 @@ impl ProArgHttp {
         // 请求头和请求体之间，用\r\n分割
         vec.extend_from_slice("\r\n\r\n".as_bytes());
         vec.extend_from_slice(body.as_ref());
-        
+
         Cow::from(vec)
     }
 
-    // 读请求头
-    fn read_head(head_data: &[u8]) -> HashMap<String, String> {
-        let mut map = HashMap::new();
-        let data_str = String::from_utf8_lossy(head_data);
-        data_str
+    // 消息体解码，处理分块传输、解压，每条消息只解码一次
+    // 返回解码后的消息体、尾部字段，以及处理过程中的说明
+    fn decode_body(message: &HttpMessage) -> (&[u8], &[u8], &[String]) {
+        let decoded = message.decoded.get_or_init(|| {
+            let mut notes = Vec::new();
+            let body_data = Cow::Borrowed(message.body.as_slice());
+            let (body_data, trailer, truncated) = Self::combin_data(message, body_data);
+            if truncated {
+                notes.push("分块传输不完整，消息体被截断".to_string());
+            }
+            let body_data = Self::decompress(&message.headers, body_data, &mut notes);
+            DecodedBody {
+                data: match body_data {
+                    Cow::Owned(data) => Some(data),
+                    Cow::Borrowed(_) => None,
+                },
+                trailer,
+                notes,
+            }
+        });
+        (
+            decoded.data.as_deref().unwrap_or(&message.body),
+            &decoded.trailer,
+            &decoded.notes,
+        )
+    }
+
+    // 请求和响应是否满足过滤条件
+    fn matches(&self, request: Option<&HttpMessage>, response: Option<&HttpMessage>) -> bool {
+        if !self.filter.matches(request, response) {
+            return false;
+        }
+        match self.filter.grep() {
+            None => true,
+            Some(grep) => [request, response]
+                .into_iter()
+                .flatten()
+                .any(|message| self.grep_message(grep, message)),
+        }
+    }
+
+    // 搜索http头或者解码后的消息体
+    fn grep_message(&self, grep: &Regex, message: &HttpMessage) -> bool {
+        let target = self.filter.grep_target;
+        if target != GrepTarget::Body && grep.is_match(&String::from_utf8_lossy(&message.head)) {
+            return true;
+        }
+        if target != GrepTarget::Head && !message.body.is_empty() {
+            let (body_data, _, _) = Self::decode_body(message);
+            let content_type = message.headers.get("Content-Type");
+            return grep.is_match(&self.u8_to_str(content_type, body_data));
+        }
+        false
+    }
+
+    // 处理一条完整的消息
+    fn message_process(&self, message: &HttpMessage) -> Vec<u8> {
+        let mut trailer = Vec::new();
+        // 处理过程中的说明，输出在消息后面
+        let mut notes = Vec::new();
+        let body = if !self.body_show {
+            Cow::from(&[] as &[u8])
+        } else if self.itself {
+            Cow::Borrowed(message.body.as_slice())
+        } else if !message.body.is_empty() {
+            // 处理分块传输、解压
+            let (body_data, chunked_trailer, decode_notes) = Self::decode_body(message);
+            trailer = chunked_trailer.to_vec();
+            notes.extend_from_slice(decode_notes);
+            let content_type = message.headers.get("Content-Type");
+            // 二进制数据不转文本
+            if binary::is_binary(content_type, body_data) {
+                let text = if self.hexdump {
+                    binary::hexdump(content_type, body_data)
+                } else {
+                    binary::summary(content_type, body_data)
+                };
+                Cow::Owned(text.into_bytes())
+            } else {
+                // 转成UTF-8，http头单独处理
+                let text = self.u8_to_str(content_type, body_data);
+                // 脱敏
+                let text = match &self.redact {
+                    Some(redact) => Cow::Owned(redact.body(content_type, &text).into_owned()),
+                    None => text,
+                };
+                // 格式化
+                let text = if self.pretty {
+                    pretty::pretty(content_type, &text).into_owned()
+                } else {
+                    text.into_owned()
+                };
+                Cow::Owned(text.into_bytes())
+            }
+        } else {
+            Cow::from(&[] as &[u8])
+        };
+
+        // 原值输出时，也对http头脱敏
+        let head = match &self.redact {
+            Some(redact) => redact.head(&message.head, true),
+            None => Cow::Borrowed(message.head.as_slice()),
+        };
+        let mut target_data = self.analyse_target(head, body);
+        // 尾部字段属于http头，跟在消息体后面
+        if self.head_show && !trailer.is_empty() {
+            let trailer = match &self.redact {
+                Some(redact) => redact.head(&trailer, false),
+                None => Cow::Borrowed(trailer.as_slice()),
+            };
+            let target_data = target_data.to_mut();
+            target_data.extend_from_slice(b"\r\n");
+            target_data.extend_from_slice(trailer.trim_ascii_end());
+        }
+        for note in notes {
+            target_data
+                .to_mut()
+                .extend_from_slice(format!("\r\n({note})").as_bytes());
+        }
+
+        if self.itself {
+            target_data.into_owned()
+        } else {
+            // 消息体已经是UTF-8
+            String::from_utf8_lossy(&target_data)
+                .into_owned()
+                .into_bytes()
+        }
+    }
+
+    // 过滤后，逐个处理请求和响应
+    fn transactions_process(&mut self, transactions: Vec<HttpTransaction>) -> Vec<Vec<u8>> {
+        // 回放的请求已经在请求完成时按请求过滤过
+        let reports: Vec<Vec<u8>> = transactions
+            .iter()
+            .filter_map(|transaction| self.replay_process(transaction))
+            .collect();
+        let transactions: Vec<HttpTransaction> = transactions
+            .into_iter()
+            .filter(|transaction| {
+                self.matches(transaction.request.as_ref(), transaction.response.as_ref())
+            })
+            .collect();
+        if self.har.is_some() {
+            for transaction in &transactions {
+                self.har_process(transaction);
+            }
+        }
+        if self.export.is_some() {
+            for transaction in &transactions {
+                self.export_process(transaction);
+            }
+        }
+        if self.replay.is_some() {
+            return reports;
+        }
+        transactions
+            .iter()
+            .flat_map(|transaction| {
+                if self.json {
+                    return self.json_process(transaction);
+                }
+                if !self.curl {
+                    return vec![self.transaction_process(transaction)];
+                }
+                transaction
+                    .request
+                    .iter()
+                    .map(|request| self.curl_process(Some(&transaction.key), request))
+                    .collect()
+            })
+            .collect()
+    }
+
+    // 请求完成时回放，按抓包时的请求顺序和间隔发送
+    // 回放结果跟着请求配对，收到响应后再和抓包时的响应比较
+    fn replay_requests(
+        &mut self,
+        messages: Vec<HttpMessage>,
+    ) -> Vec<(HttpMessage, Option<ReplayResult>)> {
+        messages
+            .into_iter()
+            .map(|message| {
+                let replayed = self.replay_request(&message);
+                (message, replayed)
+            })
+            .collect()
+    }
+
+    // 这时还没有响应，只用请求判断过滤条件
+    fn replay_request(&mut self, request: &HttpMessage) -> Option<ReplayResult> {
+        self.replay.as_ref()?;
+        if request.status().is_some() || !self.filter.matches_request(request) {
+            return None;
+        }
+        if let Some(grep) = self.filter.grep() {
+            if !self.grep_message(grep, request) {
+                return None;
+            }
+        }
+        let replay = self.replay.as_mut()?;
+        replay.wait(request.first_ts);
+        Some(replay.send(request))
+    }
+
+    // 回放结果，和抓包时的响应比较
+    fn replay_process(&self, transaction: &HttpTransaction) -> Option<Vec<u8>> {
+        let request = transaction.request.as_ref()?;
+        let replayed = transaction.replayed.as_ref()?;
+        let captured = transaction
+            .response
             .as_ref()
-            .lines()
-            // http协议，首行不是请求头，是请求方法和版本
-            .skip(1)
-            .filter_map(|s| s.split_once(':'))
-            .for_each(|(k, v)| {
-                map.insert(k.trim().to_string(), v.trim().to_string());
-            });
+            .and_then(|response| Some((response.status()?, transaction.duration()?)));
+        let request_line = match &self.redact {
+            Some(redact) => redact.line(&request.start_line.to_string()),
+            None => request.start_line.to_string(),
+        };
+        Some(replay::report(&request_line, captured, replayed).into_bytes())
+    }
+
+    // 请求转成curl命令
+    // key: 请求方向，不按TCP流重组时没有
+    fn curl_process(&self, key: Option<&StreamKey>, request: &HttpMessage) -> Vec<u8> {
+        // 只合并分块，压缩的请求体和Content-Encoding保持一致
+        let (body_data, _, _) = Self::combin_data(request, Cow::Borrowed(request.body.as_slice()));
+        let body_data = self.redact_body(request.headers.get("Content-Type"), body_data);
+        let request = self.redacted(request);
+        curl::command(&har::url(key, &request), &request, &body_data).into_bytes()
+    }
+
+    // 请求和响应分别输出一个json对象
+    fn json_process(&self, transaction: &HttpTransaction) -> Vec<Vec<u8>> {
+        let request = transaction
+            .request
+            .as_ref()
+            .map(|request| self.redacted(request));
+        let messages = [transaction.request.as_ref(), transaction.response.as_ref()];
+        messages
+            .into_iter()
+            .flatten()
+            .map(|message| {
+                let content = if self.body_show {
+                    Some(self.har_content(message))
+                } else {
+                    None
+                };
+                let mut json = json::message(
+                    transaction,
+                    &self.redacted(message),
+                    message.body.len(),
+                    content.as_ref(),
+                    request.as_deref(),
+                );
+                if !self.head_show {
+                    if let Some(json) = json.as_object_mut() {
+                        json.remove("headers");
+                    }
+                }
+                json.to_string().into_bytes()
+            })
+            .collect()
+    }
+
+    // 写入HAR文件
+    fn har_process(&mut self, transaction: &HttpTransaction) {
+        let [request, response] = [&transaction.request, &transaction.response].map(|message| {
+            message
+                .as_ref()
+                .map(|message| (self.redacted(message), self.har_content(message)))
+        });
+        let entry = har::entry(
+            transaction,
+            request
+                .as_ref()
+                .map(|(request, content)| (request.as_ref(), content)),
+            response
+                .as_ref()
+                .map(|(response, content)| (response.as_ref(), content)),
+        );
+        if let (Some(entry), Some(har)) = (entry, self.har.as_mut()) {
+            if let Err(error) = har.write(&entry) {
+                println!("写入HAR文件失败: {error}");
+            }
+        }
+    }
 
-        map
+    // 导出解码后的消息体，脱敏时只处理文本
+    fn export_process(&mut self, transaction: &HttpTransaction) {
+        let request = transaction.request.as_ref().filter(|_| self.export_request);
+        for message in [request, transaction.response.as_ref()]
+            .into_iter()
+            .flatten()
+        {
+            if message.body.is_empty() {
+                continue;
+            }
+            let (body_data, _, _) = Self::decode_body(message);
+            let body_data = self.redact_body(
+                message.headers.get("Content-Type"),
+                Cow::Borrowed(body_data),
+            );
+            if let Some(export) = self.export.as_mut() {
+                if let Err(error) = export.save(transaction, message, &body_data) {
+                    println!("导出消息体失败: {error}");
+                }
+            }
+        }
+    }
+
+    // 原始数据的消息体脱敏，只处理UTF-8文本
+    fn redact_body<'a>(&self, content_type: Option<&str>, data: Cow<'a, [u8]>) -> Cow<'a, [u8]> {
+        let redacted = match (&self.redact, std::str::from_utf8(&data)) {
+            (Some(redact), Ok(text)) => match redact.body(content_type, text) {
+                Cow::Owned(text) => Some(text),
+                Cow::Borrowed(_) => None,
+            },
+            _ => None,
+        };
+        redacted.map_or(data, |text| Cow::Owned(text.into_bytes()))
+    }
+
+    // HAR中的消息体，解码后的数据，二进制数据用base64编码
+    fn har_content(&self, message: &HttpMessage) -> HarContent {
+        let (body_data, _, _) = Self::decode_body(message);
+        let content_type = message.headers.get("Content-Type");
+        let mime_type = content_type.unwrap_or_default().to_string();
+        if binary::is_binary(content_type, body_data) {
+            return HarContent::binary(body_data, message.body.len(), mime_type);
+        }
+        let text = self.u8_to_str(content_type, body_data);
+        let text = match &self.redact {
+            Some(redact) => redact.body(content_type, &text).into_owned(),
+            None => text.into_owned(),
+        };
+        HarContent {
+            size: body_data.len(),
+            body_size: message.body.len(),
+            mime_type,
+            text,
+            base64: false,
+        }
+    }
+
+    // 脱敏后的消息，只替换首行和http头，不含消息体
+    fn redacted<'a>(&self, message: &'a HttpMessage) -> Cow<'a, HttpMessage> {
+        let Some(redact) = &self.redact else {
+            return Cow::Borrowed(message);
+        };
+        match HttpMessage::parse(&redact.head(&message.head, true)) {
+            Some(mut redacted) => {
+                redacted.complete = message.complete;
+                redacted.first_ts = message.first_ts;
+                redacted.last_ts = message.last_ts;
+                Cow::Owned(redacted)
+            }
+            None => Cow::Borrowed(message),
+        }
+    }
+
+    // 处理一次请求和响应，一起输出
+    fn transaction_process(&self, transaction: &HttpTransaction) -> Vec<u8> {
+        let mut data = Vec::new();
+        if !self.itself {
+            let summary = match &self.redact {
+                Some(redact) => redact.line(&transaction.summary()),
+                None => transaction.summary(),
+            };
+            data.extend_from_slice(summary.as_bytes());
+            data.extend_from_slice(b"\n");
+        }
+        let messages = [&transaction.request, &transaction.response];
+        for (index, message) in messages.into_iter().flatten().enumerate() {
+            if index > 0 {
+                data.extend_from_slice(b"\n\n");
+            }
+            data.extend(self.message_process(message));
+        }
+        data
     }
 }
 
 impl ProArg for ProArgHttp {
     fn byte_process<'a>(&self, data: &'a [u8]) -> Cow<'a, [u8]> {
-        // 找请求头、响应头
-        let head_end = Self::find_sep_index(data);
-        let head = &data[0..head_end];
-        let head_map = Self::read_head(head);
-
-        let body = if self.body_show && self.itself {
-            Cow::Borrowed(&data[head_end + 4..])
-        } else if self.body_show && data.len() > head_end + 4 {
-            let body_data = Cow::Borrowed(&data[head_end + 4..]);
-            // 处理分块传输
-            let body_data = Self::combin_data(&head_map, body_data);
-            // 解压
-            Self::decompress(&head_map, body_data)
-        } else {
-            Cow::from(&[] as &[u8])
+        // 不是http消息时，原样输出
+        let Some(message) = HttpMessage::parse(data) else {
+            return Cow::Borrowed(data);
+        };
+        // 单个报文只有请求或者响应
+        let matched = match message.status() {
+            None => self.matches(Some(&message), None),
+            Some(_) => self.matches(None, Some(&message)),
         };
+        if !matched {
+            return Cow::Borrowed(&[]);
+        }
+        if self.curl {
+            return match message.status() {
+                None => Cow::Owned(self.curl_process(None, &message)),
+                Some(_) => Cow::Borrowed(&[]),
+            };
+        }
+        Cow::Owned(self.message_process(&message))
+    }
 
-        let target_data = self.analyse_target(Cow::Borrowed(head), body);
-        
-        if self.itself {
-            target_data
+    fn stream_process(&mut self, key: &StreamKey, ts: Duration, data: &[u8]) -> Vec<Vec<u8>> {
+        // 连接上第一个发送数据的方向是forward
+        let forward = !self.connections.contains_key(&key.reverse());
+        let conn_key = if forward { *key } else { key.reverse() };
+        let connection = self
+            .connections
+            .entry(conn_key)
+            .or_insert_with(HttpConnection::new);
+        let (parser, other) = if forward {
+            (&mut connection.forward, &mut connection.backward)
         } else {
-            let content_type = head_map.get("Content-Type");
-            let content_type = content_type.map(|s| s.as_str());
-            let data = self.u8_to_str(content_type, &target_data);
-            // todo 优化，减少内存拷贝
-            match data {
-                // 只有源字节数组，是utf8时，才会返回Borrowed，源字节数组来自target_data，
-                // 将target_data直接返回，可以避免生命周期问题
-                Cow::Borrowed(_) => target_data,
-                Cow::Owned(s) => Cow::Owned(s.into_bytes()),
+            (&mut connection.backward, &mut connection.forward)
+        };
+        let messages = parser.push(data, ts, &mut connection.methods);
+        if parser.is_upgraded() {
+            // 协议升级后，两个方向都不再是http
+            other.upgrade();
+        }
+        let messages = self.replay_requests(messages);
+        let transactions = match self.connections.get_mut(&conn_key) {
+            Some(connection) => connection.pair(*key, messages),
+            None => Vec::new(),
+        };
+        self.transactions_process(transactions)
+    }
+
+    fn stream_close(&mut self, key: &StreamKey, _reason: CloseReason) -> Vec<Vec<u8>> {
+        let connection = self
+            .connections
+            .remove_entry(key)
+            .or_else(|| self.connections.remove_entry(&key.reverse()));
+        let Some((conn_key, mut connection)) = connection else {
+            return Vec::new();
+        };
+        // 还没有完成的消息，也参与配对
+        let mut transactions = Vec::new();
+        for (key, message) in [
+            (conn_key, connection.forward.close()),
+            (conn_key.reverse(), connection.backward.close()),
+        ] {
+            let messages = self.replay_requests(message.into_iter().collect());
+            transactions.extend(connection.pair(key, messages));
+        }
+        // 没有响应的请求
+        while let Some((key, request, replayed)) = connection.requests.pop_front() {
+            transactions.push(HttpTransaction {
+                key,
+                request: Some(request),
+                response: None,
+                replayed,
+            });
+        }
+        self.transactions_process(transactions)
+    }
+
+    // curl、回放输出的是文本，不支持json
+    fn json(&mut self) -> bool {
+        self.json = !self.curl && self.replay.is_none();
+        self.json
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::Write,
+        net::{TcpListener, TcpStream},
+        thread,
+    };
+
+    use flate2::{
+        write::{DeflateEncoder, GzEncoder, ZlibEncoder},
+        Compression,
+    };
+
+    use super::*;
+
+    fn key(client_port: u16) -> StreamKey {
+        StreamKey {
+            src: ([10, 0, 0, 1], client_port).into(),
+            dst: ([10, 0, 0, 2], 80).into(),
+        }
+    }
+
+    // 读到请求头结束
+    fn read_head(stream: &mut TcpStream) -> String {
+        let mut head = Vec::new();
+        let mut buf = [0; 1024];
+        while !head.ends_with(b"\r\n\r\n") {
+            let len = stream.read(&mut buf).unwrap();
+            if len == 0 {
+                break;
             }
+            head.extend_from_slice(&buf[..len]);
+        }
+        String::from_utf8(head).unwrap()
+    }
+
+    #[test]
+    fn replay_in_request_order() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let target = listener.local_addr().unwrap().to_string();
+        let server = thread::spawn(move || {
+            let mut request_lines = Vec::new();
+            for _ in 0..2 {
+                let (mut stream, _) = listener.accept().unwrap();
+                let head = read_head(&mut stream);
+                request_lines.push(head.lines().next().unwrap_or_default().to_string());
+                stream
+                    .write_all(b"HTTP/1.1 204 No Content\r\n\r\n")
+                    .unwrap();
+            }
+            request_lines
+        });
+
+        let mut pro_arg = ProArgHttp::new();
+        let mut replay = Replay::new(target);
+        replay.speed = 0.0;
+        pro_arg.replay = Some(replay);
+        let ts = Duration::from_secs;
+        // 连接A的请求先发出，但是响应比连接B晚
+        let (a, b) = (key(5000), key(5001));
+        let request_a = b"GET /a HTTP/1.1\r\nHost: example.com\r\n\r\n";
+        let request_b = b"GET /b HTTP/1.1\r\nHost: example.com\r\n\r\n";
+        let response = b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n";
+        assert!(pro_arg.stream_process(&a, ts(1), request_a).is_empty());
+        assert!(pro_arg.stream_process(&b, ts(2), request_b).is_empty());
+        let reports_b = pro_arg.stream_process(&b.reverse(), ts(3), response);
+        let reports_a = pro_arg.stream_process(&a.reverse(), ts(4), response);
+
+        assert_eq!(
+            server.join().unwrap(),
+            ["GET /a HTTP/1.1", "GET /b HTTP/1.1"]
+        );
+        for (reports, path) in [(reports_a, "/a"), (reports_b, "/b")] {
+            assert_eq!(reports.len(), 1);
+            let report = String::from_utf8(reports[0].clone()).unwrap();
+            assert!(report.starts_with(&format!("# 回放 GET {path} HTTP/1.1  抓包: 200")));
+            assert!(report.contains("回放: 204"));
+        }
+    }
+
+    #[test]
+    fn replay_filtered_by_request() {
+        let mut pro_arg = ProArgHttp::new();
+        // 没有服务端，回放会失败，过滤掉的请求不回放
+        pro_arg.replay = Some(Replay::new("127.0.0.1:1".to_string()));
+        pro_arg.filter.methods.push("POST".to_string());
+        let request = HttpMessage::parse(b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n").unwrap();
+        assert!(pro_arg.replay_request(&request).is_none());
+    }
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn zlib(data: &[u8]) -> Vec<u8> {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn raw_deflate(data: &[u8]) -> Vec<u8> {
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn response(headers: &str, body: Vec<u8>) -> HttpMessage {
+        let mut message =
+            HttpMessage::parse(format!("HTTP/1.1 200 OK\r\n{headers}").as_bytes()).unwrap();
+        message.body = body;
+        message
+    }
+
+    #[test]
+    fn decompress_stacked_encodings() {
+        // 先deflate再gzip，最后传输编码gzip，解码时顺序相反
+        let body = gzip(&gzip(&zlib(b"hello")));
+        let message = response(
+            "Content-Encoding: deflate, gzip\r\nTransfer-Encoding: gzip",
+            body,
+        );
+        let (body_data, _, notes) = ProArgHttp::decode_body(&message);
+        assert_eq!(body_data, b"hello");
+        assert!(notes.is_empty());
+    }
+
+    #[test]
+    fn decompress_deflate_formats() {
+        for body in [zlib(b"hello"), raw_deflate(b"hello")] {
+            let message = response("Content-Encoding: deflate", body);
+            assert_eq!(ProArgHttp::decode_body(&message).0, b"hello");
+        }
+    }
+
+    #[test]
+    fn decompress_limit() {
+        let body = gzip(&vec![0; MAX_DECOMPRESS_LEN + 10]);
+        let message = response("Content-Encoding: gzip", body);
+        let (body_data, _, notes) = ProArgHttp::decode_body(&message);
+        assert_eq!(body_data.len(), MAX_DECOMPRESS_LEN);
+        assert_eq!(
+            notes,
+            [format!("解压后超过{MAX_DECOMPRESS_LEN}字节，已截断")]
+        );
+    }
+
+    #[test]
+    fn decompress_unsupported() {
+        // 不支持的编码，之后的编码也不再处理
+        let body = gzip(b"hello");
+        let message = response("Content-Encoding: gzip, compress", body.clone());
+        let (body_data, _, notes) = ProArgHttp::decode_body(&message);
+        assert_eq!(body_data, body);
+        assert_eq!(notes, ["不支持的压缩协议: compress"]);
+
+        let message = response("Content-Encoding: identity", b"hello".to_vec());
+        let (body_data, _, notes) = ProArgHttp::decode_body(&message);
+        assert_eq!(body_data, b"hello");
+        assert!(notes.is_empty());
+    }
+
+    #[test]
+    fn decode_body_once() {
+        let body = b"5\r\nhello\r\n0\r\nX-Trailer: 1\r\n\r\n".to_vec();
+        let message = response("Transfer-Encoding: chunked", body);
+        let (first, trailer, _) = ProArgHttp::decode_body(&message);
+        assert_eq!(first, b"hello");
+        assert_eq!(trailer, b"X-Trailer: 1\r\n");
+        let (second, _, _) = ProArgHttp::decode_body(&message);
+        assert!(std::ptr::eq(first, second));
+    }
+
+    fn grep_arg(pattern: &str, target: GrepTarget) -> ProArgHttp {
+        let mut pro_arg = ProArgHttp::new();
+        pro_arg.filter.grep_pattern = Some(pattern.to_string());
+        pro_arg.filter.grep_target = target;
+        pro_arg.filter.build_grep().unwrap();
+        pro_arg
+    }
+
+    #[test]
+    fn grep_decoded_body() {
+        let message = response(
+            "Content-Type: text/plain\r\nContent-Encoding: gzip",
+            gzip(b"token=secret"),
+        );
+        assert!(!String::from_utf8_lossy(&message.body).contains("secret"));
+        let pro_arg = grep_arg("token=s.*t", GrepTarget::Body);
+        assert!(pro_arg.matches(None, Some(&message)));
+        let pro_arg = grep_arg("other", GrepTarget::Body);
+        assert!(!pro_arg.matches(None, Some(&message)));
+    }
+
+    #[test]
+    fn grep_options() {
+        let message = response("X-Trace: abc", b"Hello (World)".to_vec());
+        let mut pro_arg = grep_arg("hello", GrepTarget::Body);
+        assert!(!pro_arg.matches(None, Some(&message)));
+        pro_arg.filter.grep_ignore_case = true;
+        pro_arg.filter.build_grep().unwrap();
+        assert!(pro_arg.matches(None, Some(&message)));
+
+        // 按普通字符串搜索时，.不是正则表达式
+        let mut pro_arg = grep_arg("(Wor.d)", GrepTarget::Body);
+        assert!(pro_arg.matches(None, Some(&message)));
+        pro_arg.filter.grep_fixed = true;
+        pro_arg.filter.build_grep().unwrap();
+        assert!(!pro_arg.matches(None, Some(&message)));
+        pro_arg.filter.grep_pattern = Some("(World)".to_string());
+        pro_arg.filter.build_grep().unwrap();
+        assert!(pro_arg.matches(None, Some(&message)));
+
+        let mut filter = HttpFilter::default();
+        filter.grep_pattern = Some("(".to_string());
+        assert!(filter.build_grep().is_err());
+    }
+
+    #[test]
+    fn grep_target() {
+        let request =
+            HttpMessage::parse(b"GET /find HTTP/1.1\r\nX-Trace: abc\r\n\r\nbody").unwrap();
+        for (pattern, target, expect) in [
+            ("X-Trace", GrepTarget::Head, true),
+            ("X-Trace", GrepTarget::Body, false),
+            ("X-Trace", GrepTarget::All, true),
+            ("body", GrepTarget::Head, false),
+            ("body", GrepTarget::Body, true),
+            ("body", GrepTarget::All, true),
+            ("/find", GrepTarget::Head, true),
+        ] {
+            let pro_arg = grep_arg(pattern, target);
+            assert_eq!(
+                pro_arg.matches(Some(&request), None),
+                expect,
+                "{pattern} {target:?}"
+            );
         }
     }
 }
@@ -0,0 +1,180 @@
+use sha2::{Digest, Sha256};
+
+use crate::process::change_data;
+
+use super::{charset, SNIFF_LEN};
+
+// hexdump最多显示的长度
+const HEXDUMP_MAX_LEN: usize = 512;
+// 文本类型
+const TEXT_TYPES: [&str; 6] = [
+    "application/json",
+    "application/xml",
+    "application/javascript",
+    "application/x-www-form-urlencoded",
+    "application/graphql",
+    "application/x-ndjson",
+];
+// 二进制类型的前缀
+const BINARY_TYPE_PREFIXES: [&str; 4] = ["image/", "audio/", "video/", "font/"];
+// 二进制类型
+const BINARY_TYPES: [&str; 10] = [
+    "application/octet-stream",
+    "application/protobuf",
+    "application/x-protobuf",
+    "application/grpc",
+    "application/zip",
+    "application/gzip",
+    "application/pdf",
+    "application/wasm",
+    "application/msgpack",
+    "application/vnd.google.protobuf",
+];
+// 根据开头的字节识别类型
+const MAGICS: [(&[u8], &str); 10] = [
+    (b"\x89PNG\r\n\x1a\n", "image/png"),
+    (b"\xff\xd8\xff", "image/jpeg"),
+    (b"GIF8", "image/gif"),
+    (b"%PDF-", "application/pdf"),
+    (b"PK\x03\x04", "application/zip"),
+    (b"\x1f\x8b", "application/gzip"),
+    (b"\x28\xb5\x2f\xfd", "application/zstd"),
+    (b"\x00asm", "application/wasm"),
+    (b"BM", "image/bmp"),
+    (b"OggS", "audio/ogg"),
+];
+
+// 是否是二进制数据，先看Content-Type，不确定时检查内容
+pub fn is_binary(content_type: Option<&str>, data: &[u8]) -> bool {
+    if data.is_empty() {
+        return false;
+    }
+    let mime = mime(content_type);
+    if mime.starts_with("text/")
+        || mime.ends_with("+json")
+        || mime.ends_with("+xml")
+        || TEXT_TYPES.contains(&mime.as_str())
+    {
+        return false;
+    }
+    if BINARY_TYPE_PREFIXES
+        .iter()
+        .any(|prefix| mime.starts_with(prefix))
+        || BINARY_TYPES.contains(&mime.as_str())
+        || mime.ends_with("+proto")
+    {
+        return true;
+    }
+    // 有BOM的UTF-16、UTF-8是文本
+    if data.starts_with(b"\xff\xfe") || data.starts_with(b"\xfe\xff") {
+        return false;
+    }
+    let sniff = &data[..data.len().min(SNIFF_LEN)];
+    // 截取时，最后一个字符可能不完整
+    let utf8 = match std::str::from_utf8(sniff) {
+        Ok(_) => true,
+        Err(error) => error.error_len().is_none(),
+    };
+    if utf8 && !sniff.contains(&0) {
+        return false;
+    }
+    // 没有BOM的UTF-16，ASCII字符的高字节是0，不能按控制字符判断
+    if charset::guess_utf16(sniff).is_some() {
+        return false;
+    }
+    // 控制字符较多时认为是二进制，多字节编码的文本没有控制字符
+    let control = sniff
+        .iter()
+        .filter(|b| b.is_ascii_control() && !b"\t\n\r\x0c\x1b".contains(b))
+        .count();
+    control * 10 > sniff.len()
+}
+
+// 二进制数据摘要，类型、大小、SHA-256
+pub fn summary(content_type: Option<&str>, data: &[u8]) -> String {
+    let sha256 = Sha256::digest(data)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect::<String>();
+    format!(
+        "(二进制数据，类型: {}，大小: {}字节，SHA-256: {sha256})",
+        binary_type(content_type, data),
+        data.len()
+    )
+}
+
+// hexdump，只显示开头的部分
+pub fn hexdump(content_type: Option<&str>, data: &[u8]) -> String {
+    let mut out = change_data::hexdump(&data[..data.len().min(HEXDUMP_MAX_LEN)]);
+    out.push('\n');
+    if data.len() > HEXDUMP_MAX_LEN {
+        out.push_str(&format!(
+            "(二进制数据，类型: {}，大小: {}字节，只显示前{HEXDUMP_MAX_LEN}字节)",
+            binary_type(content_type, data),
+            data.len()
+        ));
+    } else {
+        out.push_str(&format!(
+            "(二进制数据，类型: {}，大小: {}字节)",
+            binary_type(content_type, data),
+            data.len()
+        ));
+    }
+    out
+}
+
+// 数据类型，Content-Type不明确时，根据内容识别
+fn binary_type(content_type: Option<&str>, data: &[u8]) -> String {
+    let mime = mime(content_type);
+    if !mime.is_empty() && mime != "application/octet-stream" {
+        return mime;
+    }
+    MAGICS
+        .iter()
+        .find(|(magic, _)| data.starts_with(magic))
+        .map(|(_, name)| name.to_string())
+        .unwrap_or_else(|| "application/octet-stream".to_string())
+}
+
+// Content-Type中的类型，不含参数
+pub(super) fn mime(content_type: Option<&str>) -> String {
+    content_type
+        .and_then(|content_type| content_type.split(';').next())
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utf16(text: &str, big_endian: bool) -> Vec<u8> {
+        text.encode_utf16()
+            .flat_map(|unit| {
+                if big_endian {
+                    unit.to_be_bytes()
+                } else {
+                    unit.to_le_bytes()
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn utf16_without_bom_is_text() {
+        let text = "{\"name\": \"value\", \"list\": [1, 2, 3]}";
+        assert!(!is_binary(None, &utf16(text, false)));
+        assert!(!is_binary(
+            Some("application/x-unknown"),
+            &utf16(text, true)
+        ));
+    }
+
+    #[test]
+    fn control_bytes_are_binary() {
+        assert!(is_binary(None, b"\x00\x01\x02\x03\x00\x00\x10\x11\x00\x05"));
+        assert!(is_binary(Some("image/png"), b"\x89PNG\r\n\x1a\n"));
+        assert!(!is_binary(None, "中文 text\r\n".as_bytes()));
+    }
+}
@@ -0,0 +1,177 @@
+use std::borrow::Cow;
+
+use encoding_rs::{Encoding, BIG5, GB18030, SHIFT_JIS, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252};
+
+use super::SNIFF_LEN;
+
+// 没有指定编码，也不是UTF-8时，依次尝试的编码
+const GUESS_ENCODINGS: [&Encoding; 3] = [GB18030, SHIFT_JIS, BIG5];
+
+// 根据名称获取编码，支持GBK、GB18030、Big5、Shift_JIS、ISO-8859-x、UTF-16等常见名称
+pub fn from_label(label: &str) -> Option<&'static Encoding> {
+    Encoding::for_label(label.trim().trim_matches(['"', '\'']).as_bytes())
+}
+
+// 从Content-Type中获取编码，Content-Type: text/html; charset=GBK
+pub fn from_content_type(content_type: &str) -> Option<&'static Encoding> {
+    content_type.split(';').skip(1).find_map(|param| {
+        let (key, value) = param.split_once('=')?;
+        if key.trim().eq_ignore_ascii_case("charset") {
+            from_label(value)
+        } else {
+            None
+        }
+    })
+}
+
+// 转成UTF-8文本
+// force: 强制使用的编码，没有时使用Content-Type中的编码，都没有时猜测编码
+pub fn decode<'a>(
+    force: Option<&'static Encoding>,
+    content_type: Option<&str>,
+    data: &'a [u8],
+) -> Cow<'a, str> {
+    let encoding = force
+        .or_else(|| content_type.and_then(from_content_type))
+        .unwrap_or_else(|| guess(data));
+    // 有BOM时，以BOM为准
+    let (text, _, _) = encoding.decode(data);
+    text
+}
+
+// 猜测编码
+fn guess(data: &[u8]) -> &'static Encoding {
+    if let Some((encoding, _)) = Encoding::for_bom(data) {
+        return encoding;
+    }
+    let sniff = &data[..data.len().min(SNIFF_LEN)];
+    // html的meta、xml声明中的编码
+    if sniff.trim_ascii_start().starts_with(b"<") {
+        if let Some(encoding) = declared(sniff) {
+            return encoding;
+        }
+    }
+    if let Some(encoding) = guess_utf16(sniff) {
+        return encoding;
+    }
+    if std::str::from_utf8(data).is_ok() {
+        return UTF_8;
+    }
+    GUESS_ENCODINGS
+        .into_iter()
+        .find(|encoding| {
+            encoding
+                .decode_without_bom_handling_and_without_replacement(data)
+                .is_some()
+        })
+        // 单字节编码，任何数据都能解码
+        .unwrap_or(WINDOWS_1252)
+}
+
+// 文档中声明的编码
+// <meta charset="GBK">、<meta http-equiv="Content-Type" content="text/html; charset=GBK">
+// <?xml version="1.0" encoding="GBK"?>
+fn declared(sniff: &[u8]) -> Option<&'static Encoding> {
+    let text = String::from_utf8_lossy(sniff).to_ascii_lowercase();
+    ["charset=", "encoding="].iter().find_map(|key| {
+        let index = text.find(key)? + key.len();
+        let value = text[index..].trim_start_matches(['"', '\'']);
+        let end = value
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == '_'))
+            .unwrap_or(value.len());
+        let encoding = from_label(&value[..end])?;
+        // 能读到声明，说明文档不是UTF-16
+        if encoding == UTF_16LE || encoding == UTF_16BE {
+            None
+        } else {
+            Some(encoding)
+        }
+    })
+}
+
+// 没有BOM的UTF-16，ASCII字符的高字节是0
+pub fn guess_utf16(sniff: &[u8]) -> Option<&'static Encoding> {
+    let pairs = sniff.len() / 2;
+    if pairs < 2 {
+        return None;
+    }
+    let even_zero = sniff.iter().step_by(2).filter(|b| **b == 0).count();
+    let odd_zero = sniff.iter().skip(1).step_by(2).filter(|b| **b == 0).count();
+    // 一半以上是0，另一个位置几乎没有0
+    if odd_zero * 2 > pairs && even_zero * 10 < pairs {
+        Some(UTF_16LE)
+    } else if even_zero * 2 > pairs && odd_zero * 10 < pairs {
+        Some(UTF_16BE)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use encoding_rs::GBK;
+
+    use super::*;
+
+    #[test]
+    fn content_type_charset() {
+        for (content_type, expect) in [
+            ("text/html; charset=GBK", Some("GBK")),
+            ("text/html;charset=\"utf-8\"", Some("UTF-8")),
+            (
+                "text/plain; format=flowed; Charset=Shift_JIS",
+                Some("Shift_JIS"),
+            ),
+            ("text/html", None),
+            ("text/html; charset=unknown", None),
+            // 没有参数时，charset只是类型的一部分
+            ("charset=GBK", None),
+        ] {
+            assert_eq!(
+                from_content_type(content_type).map(Encoding::name),
+                expect,
+                "{content_type}"
+            );
+        }
+    }
+
+    #[test]
+    fn declared_charset() {
+        let (gbk, _, _) = GB18030.encode("<html><head><meta charset=\"gbk\"></head>你好</html>");
+        assert_eq!(guess(&gbk), GBK);
+        assert_eq!(
+            decode(None, Some("text/html"), &gbk),
+            "<html><head><meta charset=\"gbk\"></head>你好</html>"
+        );
+
+        let xml = b"<?xml version='1.0' encoding='Shift_JIS'?><a/>";
+        assert_eq!(guess(xml), SHIFT_JIS);
+        // 能读到的UTF-16声明不可信
+        let xml = b"<?xml version=\"1.0\" encoding=\"UTF-16\"?><a/>";
+        assert_eq!(guess(xml), UTF_8);
+        // 不是标记语言时不看声明
+        assert_eq!(guess(b"charset=gbk"), UTF_8);
+    }
+
+    #[test]
+    fn guess_encoding() {
+        assert_eq!(guess(b"\xEF\xBB\xBFhello"), UTF_8);
+        assert_eq!(guess(b"\xFF\xFEh\0i\0"), UTF_16LE);
+        assert_eq!(guess(b"h\0e\0l\0l\0o\0"), UTF_16LE);
+        assert_eq!(guess(b"\0h\0e\0l\0l\0o"), UTF_16BE);
+        assert_eq!(guess("你好".as_bytes()), UTF_8);
+        let (gbk, _, _) = GB18030.encode("你好，世界");
+        assert_eq!(guess(&gbk), GB18030);
+        // 多字节编码都解不了时，按单字节编码
+        assert_eq!(guess(b"a\xFF"), WINDOWS_1252);
+        // 强制的编码优先
+        assert_eq!(
+            decode(
+                Some(WINDOWS_1252),
+                Some("text/plain; charset=utf-8"),
+                "é".as_bytes()
+            ),
+            "Ã©"
+        );
+    }
+}
@@ -0,0 +1,141 @@
+// 分块大小行最多的16进制位数，超过时认为格式错误
+const MAX_CHUNK_SIZE_DIGITS: usize = 16;
+
+// 分块传输解码后的消息体
+#[derive(Debug)]
+pub struct ChunkedBody {
+    // 所有分块合并后的数据
+    pub data: Vec<u8>,
+    // 尾部字段，结束分块之后的字段行，原样保留
+    pub trailer: Vec<u8>,
+    // 报文不完整或者格式错误，没有读到结束分块
+    pub truncated: bool,
+}
+
+// Transfer-Encoding: chunked 解码
+// 格式: 分块大小(16进制)[;扩展]\r\n 数据\r\n ... 0\r\n 尾部字段\r\n \r\n
+// 消息体已经完整或者被截断，一次解码。按TCP流解析时，消息的结束位置由MessageParser确定
+pub fn decode(data: &[u8]) -> ChunkedBody {
+    let mut body = ChunkedBody {
+        data: Vec::with_capacity(data.len()),
+        trailer: Vec::new(),
+        truncated: true,
+    };
+    let mut rest = data;
+    loop {
+        let Some((line, next)) = split_line(rest) else {
+            return body;
+        };
+        let Some(size) = chunk_size(line) else {
+            return body;
+        };
+        rest = next;
+        if size == 0 {
+            body.trailer = trailer(rest).to_vec();
+            body.truncated = false;
+            return body;
+        }
+        if rest.len() < size {
+            // 最后一个分块不完整，保留已经收到的部分
+            body.data.extend_from_slice(rest);
+            return body;
+        }
+        body.data.extend_from_slice(&rest[..size]);
+        rest = &rest[size..];
+        // 分块数据后面的换行
+        rest = rest
+            .strip_prefix(b"\r\n")
+            .or_else(|| rest.strip_prefix(b"\n"))
+            .unwrap_or(rest);
+    }
+}
+
+// 读取一行，兼容只有\n的换行，返回行内容和剩余数据
+fn split_line(data: &[u8]) -> Option<(&[u8], &[u8])> {
+    let index = data.iter().position(|b| *b == b'\n')?;
+    let line = &data[..index];
+    let line = line.strip_suffix(b"\r").unwrap_or(line);
+    Some((line, &data[index + 1..]))
+}
+
+// 分块大小，忽略分块扩展，MessageParser也用它确定分块长度
+pub fn chunk_size(line: &[u8]) -> Option<usize> {
+    let line = std::str::from_utf8(line).ok()?;
+    let size = line.split(';').next()?.trim();
+    if size.is_empty() || size.len() > MAX_CHUNK_SIZE_DIGITS {
+        return None;
+    }
+    usize::from_str_radix(size, 16).ok()
+}
+
+// 尾部字段，到空行为止，不含空行
+fn trailer(data: &[u8]) -> &[u8] {
+    let mut rest = data;
+    while let Some((line, next)) = split_line(rest) {
+        if line.is_empty() {
+            return &data[..data.len() - rest.len()];
+        }
+        rest = next;
+    }
+    // 没有结束的空行，剩余的都是尾部字段
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn multiple_chunks() {
+        let body = decode(b"5\r\nhello\r\n1\r\n \r\nA\r\n0123456789\r\n0\r\n\r\n");
+        assert_eq!(body.data, b"hello 0123456789");
+        assert!(body.trailer.is_empty());
+        assert!(!body.truncated);
+    }
+
+    #[test]
+    fn chunk_extensions() {
+        let body = decode(b"5;name=value\r\nhello\r\n3 ; a ; b=\"c\"\r\nabc\r\n0;last\r\n\r\n");
+        assert_eq!(body.data, b"helloabc");
+        assert!(!body.truncated);
+        // 只用\n换行
+        let body = decode(b"2;x\nhi\n0\n\n");
+        assert_eq!(body.data, b"hi");
+        assert!(!body.truncated);
+    }
+
+    #[test]
+    fn trailers() {
+        let body = decode(b"2\r\nhi\r\n0\r\nX-Checksum: 1\r\nX-Other: 2\r\n\r\nnext");
+        assert_eq!(body.data, b"hi");
+        assert_eq!(body.trailer, b"X-Checksum: 1\r\nX-Other: 2\r\n");
+        assert!(!body.truncated);
+        // 没有结束的空行
+        let body = decode(b"0\r\nX-Checksum: 1\r\n");
+        assert_eq!(body.trailer, b"X-Checksum: 1\r\n");
+    }
+
+    #[test]
+    fn truncated_last_chunk() {
+        let body = decode(b"5\r\nhello\r\nA\r\n01234");
+        assert_eq!(body.data, b"hello01234");
+        assert!(body.truncated);
+        // 没有结束分块
+        let body = decode(b"5\r\nhello\r\n");
+        assert_eq!(body.data, b"hello");
+        assert!(body.truncated);
+        // 大小行不完整
+        let body = decode(b"5\r\nhello\r\n1");
+        assert_eq!(body.data, b"hello");
+        assert!(body.truncated);
+    }
+
+    #[test]
+    fn malformed_size() {
+        let body = decode(b"5\r\nhello\r\nzz\r\nmore");
+        assert_eq!(body.data, b"hello");
+        assert!(body.truncated);
+        assert_eq!(chunk_size(b"11111111111111111"), None);
+        assert_eq!(chunk_size(b"ff;ext"), Some(255));
+    }
+}
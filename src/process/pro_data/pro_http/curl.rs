@@ -0,0 +1,159 @@
+use super::message::HttpMessage;
+
+// curl会自动生成的头，不输出
+const SKIP_HEADERS: [&str; 3] = ["Host", "Content-Length", "Transfer-Encoding"];
+
+// 请求转成可以执行的curl命令
+// url: 完整的url，body: 合并分块后的请求体，保持Content-Encoding对应的编码
+pub fn command(url: &str, request: &HttpMessage, body: &[u8]) -> String {
+    let mut args = vec![quote(url)];
+    match request.method().unwrap_or("GET") {
+        "GET" if body.is_empty() => {}
+        // 有请求体时，curl默认是POST
+        "POST" if !body.is_empty() => {}
+        // -X HEAD时curl会等待响应体
+        "HEAD" => args.push("--head".to_string()),
+        method => args.push(format!("-X {}", quote(method))),
+    }
+    for (name, value) in request.headers.iter() {
+        if SKIP_HEADERS
+            .iter()
+            .any(|skip| skip.eq_ignore_ascii_case(name))
+        {
+            continue;
+        }
+        args.push(format!("-H {}", quote(&format!("{name}: {value}"))));
+    }
+    if request
+        .headers
+        .get("Accept-Encoding")
+        .is_some_and(|value| !value.trim().eq_ignore_ascii_case("identity"))
+    {
+        // 自动解压响应
+        args.push("--compressed".to_string());
+    }
+    if body.is_empty() {
+        return format!("curl {}", args.join(" \\\n  "));
+    }
+    if is_text(body) {
+        args.push(format!(
+            "--data-binary {}",
+            quote(&String::from_utf8_lossy(body))
+        ));
+        return format!("curl {}", args.join(" \\\n  "));
+    }
+    // 二进制数据用printf生成，参数中不能有\0
+    args.push("--data-binary @-".to_string());
+    format!(
+        "printf {} | curl {}",
+        printf_escape(body),
+        args.join(" \\\n  ")
+    )
+}
+
+// 是否可以直接放到命令行中
+fn is_text(data: &[u8]) -> bool {
+    match std::str::from_utf8(data) {
+        Ok(text) => !text
+            .chars()
+            .any(|c| c.is_control() && !matches!(c, '\t' | '\r' | '\n')),
+        Err(_) => false,
+    }
+}
+
+// shell单引号，其中的单引号写成 '\''
+fn quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
+
+// printf的格式字符串，不可见字符用8进制转义
+fn printf_escape(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len() * 4 + 2);
+    out.push('\'');
+    for byte in data {
+        match byte {
+            b'\'' => out.push_str("\\047"),
+            b'\\' => out.push_str("\\\\"),
+            b'%' => out.push_str("%%"),
+            0x20..=0x7e => out.push(*byte as char),
+            _ => out.push_str(&format!("\\{byte:03o}")),
+        }
+    }
+    out.push('\'');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(head: &str) -> HttpMessage {
+        HttpMessage::parse(head.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn quote_single_quote() {
+        assert_eq!(quote("it's"), r"'it'\''s'");
+        assert_eq!(quote("a\\b %s $HOME"), r"'a\b %s $HOME'");
+        assert_eq!(quote(""), "''");
+    }
+
+    #[test]
+    fn printf_escape_binary() {
+        assert_eq!(
+            printf_escape(b"a'b\\c%d\0\xff\n"),
+            r"'a\047b\\c%%d\000\377\012'"
+        );
+    }
+
+    #[test]
+    fn method_selection() {
+        let url = "http://example.com/";
+        let get = request("GET / HTTP/1.1\r\nHost: example.com\r\n\r\n");
+        assert_eq!(command(url, &get, b""), "curl 'http://example.com/'");
+        let head = request("HEAD / HTTP/1.1\r\nHost: example.com\r\n\r\n");
+        assert_eq!(
+            command(url, &head, b""),
+            "curl 'http://example.com/' \\\n  --head"
+        );
+        let post = request("POST / HTTP/1.1\r\nContent-Length: 3\r\n\r\n");
+        assert_eq!(
+            command(url, &post, b"a=1"),
+            "curl 'http://example.com/' \\\n  --data-binary 'a=1'"
+        );
+        // POST没有请求体，GET有请求体时，都需要-X
+        assert_eq!(
+            command(url, &post, b""),
+            "curl 'http://example.com/' \\\n  -X 'POST'"
+        );
+        assert_eq!(
+            command(url, &get, b"x"),
+            "curl 'http://example.com/' \\\n  -X 'GET' \\\n  --data-binary 'x'"
+        );
+        let delete = request("DELETE / HTTP/1.1\r\n\r\n");
+        assert_eq!(
+            command(url, &delete, b""),
+            "curl 'http://example.com/' \\\n  -X 'DELETE'"
+        );
+    }
+
+    #[test]
+    fn headers_and_body() {
+        let request = request(
+            "POST /it's HTTP/1.1\r\nHost: example.com\r\nContent-Length: 9\r\n\
+             X-Quote: it's\r\nAccept-Encoding: gzip\r\n\r\n",
+        );
+        assert_eq!(
+            command("http://example.com/it's", &request, b"it's 100%"),
+            "curl 'http://example.com/it'\\''s' \\\n  -H 'X-Quote: it'\\''s' \\\n  \
+             -H 'Accept-Encoding: gzip' \\\n  --compressed \\\n  --data-binary 'it'\\''s 100%'"
+        );
+        // 二进制请求体通过printf传给curl
+        assert_eq!(
+            command("http://example.com/it's", &request, b"\0%'"),
+            "printf '\\000%%\\047' | curl 'http://example.com/it'\\''s' \\\n  \
+             -H 'X-Quote: it'\\''s' \\\n  -H 'Accept-Encoding: gzip' \\\n  --compressed \\\n  \
+             --data-binary @-"
+        );
+    }
+}
@@ -0,0 +1,328 @@
+use regex::{Regex, RegexBuilder};
+
+use super::message::HttpMessage;
+
+// http过滤条件，解析消息后过滤
+// 请求的条件（方法、主机、路径）用请求判断，状态码用响应判断，头在请求和响应中任意一个满足即可
+// 配对后整个请求和响应一起判断，缺少需要判断的一方时，认为不满足
+#[derive(Debug, Default)]
+pub struct HttpFilter {
+    // 请求方法，满足任意一个即可，忽略大小写
+    pub methods: Vec<String>,
+    // 主机，支持通配符，忽略大小写
+    pub host: Option<String>,
+    // 路径，不含查询参数，支持通配符
+    pub path: Option<String>,
+    // 状态码范围，满足任意一个即可
+    pub status: Vec<(u16, u16)>,
+    // http头，需要全部满足
+    pub headers: Vec<HeaderFilter>,
+    // 搜索内容，请求和响应任意一个满足即可，消息体在分块合并、解压后搜索
+    pub grep_pattern: Option<String>,
+    // 按普通字符串搜索，不是正则表达式
+    pub grep_fixed: bool,
+    // 搜索时忽略大小写
+    pub grep_ignore_case: bool,
+    // 搜索范围
+    pub grep_target: GrepTarget,
+    // 根据上面的参数生成
+    grep: Option<Regex>,
+}
+
+// 搜索范围
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum GrepTarget {
+    // 消息体
+    #[default]
+    Body,
+    // http头
+    Head,
+    // http头和消息体
+    All,
+}
+
+impl GrepTarget {
+    pub fn from_name(name: &str) -> Option<GrepTarget> {
+        match name {
+            "body" => Some(GrepTarget::Body),
+            "head" => Some(GrepTarget::Head),
+            "all" => Some(GrepTarget::All),
+            _ => None,
+        }
+    }
+}
+
+impl HttpFilter {
+    // 读取完所有参数后，生成搜索用的正则表达式
+    pub fn build_grep(&mut self) -> Result<(), String> {
+        let Some(pattern) = &self.grep_pattern else {
+            return Ok(());
+        };
+        let pattern = if self.grep_fixed {
+            regex::escape(pattern)
+        } else {
+            pattern.clone()
+        };
+        let grep = RegexBuilder::new(&pattern)
+            .case_insensitive(self.grep_ignore_case)
+            .build()
+            .map_err(|e| e.to_string())?;
+        self.grep = Some(grep);
+        Ok(())
+    }
+
+    // 搜索用的正则表达式
+    pub fn grep(&self) -> Option<&Regex> {
+        self.grep.as_ref()
+    }
+
+    // 状态码，逗号分隔，支持 404、5xx、200-299
+    pub fn parse_status(text: &str) -> Option<Vec<(u16, u16)>> {
+        text.split(',')
+            .map(str::trim)
+            .map(|status| {
+                if let Some((min, max)) = status.split_once('-') {
+                    return Some((min.trim().parse().ok()?, max.trim().parse().ok()?));
+                }
+                if let Some(class) = status.strip_suffix("xx").or(status.strip_suffix("XX")) {
+                    let min = class.parse::<u16>().ok()?.checked_mul(100)?;
+                    return Some((min, min.checked_add(99)?));
+                }
+                let status = status.parse().ok()?;
+                Some((status, status))
+            })
+            .collect()
+    }
+
+    // 请求和响应是否满足条件
+    pub fn matches(&self, request: Option<&HttpMessage>, response: Option<&HttpMessage>) -> bool {
+        if !self.request_matches(request) {
+            return false;
+        }
+        if !self.status.is_empty() {
+            let Some(status) = response.and_then(HttpMessage::status) else {
+                return false;
+            };
+            if !self
+                .status
+                .iter()
+                .any(|(min, max)| (*min..=*max).contains(&status))
+            {
+                return false;
+            }
+        }
+        self.headers.iter().all(|header_filter| {
+            [request, response]
+                .into_iter()
+                .flatten()
+                .any(|message| header_filter.matches(message))
+        })
+    }
+
+    // 只用请求判断，还没有响应时使用，状态码不参与，http头只在请求中判断
+    pub fn matches_request(&self, request: &HttpMessage) -> bool {
+        self.request_matches(Some(request))
+            && self
+                .headers
+                .iter()
+                .all(|header_filter| header_filter.matches(request))
+    }
+
+    // 请求的条件：方法、主机、路径
+    fn request_matches(&self, request: Option<&HttpMessage>) -> bool {
+        if !self.methods.is_empty() {
+            let Some(method) = request.and_then(HttpMessage::method) else {
+                return false;
+            };
+            if !self.methods.iter().any(|m| m.eq_ignore_ascii_case(method)) {
+                return false;
+            }
+        }
+        if let Some(host) = &self.host {
+            let Some(request_host) = request.and_then(HttpMessage::host) else {
+                return false;
+            };
+            if !glob_match(
+                &host.to_ascii_lowercase(),
+                &request_host.to_ascii_lowercase(),
+            ) {
+                return false;
+            }
+        }
+        if let Some(path) = &self.path {
+            let Some(request_path) = request.and_then(HttpMessage::path) else {
+                return false;
+            };
+            if !glob_match(path, request_path) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+// http头过滤条件
+#[derive(Debug)]
+pub struct HeaderFilter {
+    name: String,
+    value: HeaderValue,
+}
+
+#[derive(Debug)]
+enum HeaderValue {
+    // 只要有这个头
+    Exists,
+    // 值相等
+    Equal(String),
+    // 值匹配正则表达式
+    Regex(Regex),
+}
+
+impl HeaderFilter {
+    // Name、Name=value、Name=~regex
+    pub fn parse(text: &str) -> Result<HeaderFilter, String> {
+        let Some((name, value)) = text.split_once('=') else {
+            return Ok(HeaderFilter {
+                name: text.trim().to_string(),
+                value: HeaderValue::Exists,
+            });
+        };
+        let value = match value.strip_prefix('~') {
+            Some(regex) => HeaderValue::Regex(Regex::new(regex).map_err(|e| e.to_string())?),
+            None => HeaderValue::Equal(value.to_string()),
+        };
+        Ok(HeaderFilter {
+            name: name.trim().to_string(),
+            value,
+        })
+    }
+
+    fn matches(&self, message: &HttpMessage) -> bool {
+        let mut values = message.headers.get_all(&self.name);
+        match &self.value {
+            HeaderValue::Exists => values.next().is_some(),
+            HeaderValue::Equal(expect) => values.any(|value| value == expect),
+            HeaderValue::Regex(regex) => values.any(|value| regex.is_match(value)),
+        }
+    }
+}
+
+// 通配符匹配，*匹配任意个字符，?匹配一个字符
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // 上一个*的位置，以及当时匹配到的文本位置
+    let mut star = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                // 回到上一个*，多匹配一个字符
+                Some((star_p, star_t)) => {
+                    star = Some((star_p, star_t + 1));
+                    p = star_p + 1;
+                    t = star_t + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(path: &str, headers: &str) -> HttpMessage {
+        HttpMessage::parse(
+            format!("GET {path} HTTP/1.1\r\nHost: Api.Example.com\r\n{headers}").as_bytes(),
+        )
+        .unwrap()
+    }
+
+    fn response(status: u16, headers: &str) -> HttpMessage {
+        HttpMessage::parse(format!("HTTP/1.1 {status} X\r\n{headers}").as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn glob() {
+        assert!(glob_match("*.example.com", "api.example.com"));
+        assert!(!glob_match("*.example.com", "example.com"));
+        assert!(glob_match("/api/*/items", "/api/v1/items"));
+        assert!(glob_match("/api/*/items", "/api/v1/x/items"));
+        assert!(glob_match("/v?/*", "/v2/"));
+        assert!(!glob_match("/v?/*", "/v10"));
+        assert!(glob_match("a*b*c", "aXbYbZc"));
+        assert!(glob_match("**", ""));
+        assert!(!glob_match("?", ""));
+        assert!(glob_match("接口/?", "接口/一"));
+    }
+
+    #[test]
+    fn status_ranges() {
+        assert_eq!(HttpFilter::parse_status("404"), Some(vec![(404, 404)]));
+        assert_eq!(
+            HttpFilter::parse_status("4xx, 5XX"),
+            Some(vec![(400, 499), (500, 599)])
+        );
+        assert_eq!(
+            HttpFilter::parse_status("200-299,304"),
+            Some(vec![(200, 299), (304, 304)])
+        );
+        for invalid in ["", "abc", "2xx-3xx", "200-", "99999", "x", "700xx"] {
+            assert_eq!(HttpFilter::parse_status(invalid), None, "{invalid}");
+        }
+    }
+
+    #[test]
+    fn header_filter() {
+        let message = request("/", "X-Token: abc123\r\n");
+        for (spec, expect) in [
+            ("x-token", true),
+            ("X-TOKEN=abc123", true),
+            ("x-token=ABC123", false),
+            ("x-token=~^abc[0-9]+$", true),
+            ("x-token=~^[0-9]", false),
+            ("x-other", false),
+        ] {
+            let filter = HeaderFilter::parse(spec).unwrap();
+            assert_eq!(filter.matches(&message), expect, "{spec}");
+        }
+        assert!(HeaderFilter::parse("x-token=~(").is_err());
+    }
+
+    #[test]
+    fn matches() {
+        let mut filter = HttpFilter {
+            methods: vec!["get".to_string()],
+            host: Some("*.EXAMPLE.com".to_string()),
+            path: Some("/api/*".to_string()),
+            status: HttpFilter::parse_status("2xx").unwrap(),
+            ..HttpFilter::default()
+        };
+        filter
+            .headers
+            .push(HeaderFilter::parse("Content-Type=~json").unwrap());
+        let items = request("/api/items?id=1", "");
+        let ok = response(200, "Content-Type: application/json\r\n");
+        assert!(filter.matches(Some(&items), Some(&ok)));
+        // 状态码不满足，缺少响应
+        let not_found = response(404, "Content-Type: application/json\r\n");
+        assert!(!filter.matches(Some(&items), Some(&not_found)));
+        assert!(!filter.matches(Some(&items), None));
+        // 只用请求判断时，不看状态码和响应的http头
+        assert!(!filter.matches_request(&items));
+        filter.headers.clear();
+        assert!(filter.matches_request(&items));
+        assert!(!filter.matches_request(&request("/other", "")));
+    }
+}
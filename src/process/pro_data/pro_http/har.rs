@@ -0,0 +1,422 @@
+use std::{
+    fs::File,
+    io::{self, Seek, SeekFrom, Write},
+    time::Duration,
+};
+
+use chrono::{DateTime, Local, SecondsFormat};
+use serde_json::{json, Value};
+
+use crate::{process::change_data::base64, stream::StreamKey};
+
+use super::{
+    message::{HttpMessage, StartLine},
+    pretty,
+    transaction::HttpTransaction,
+};
+
+// 文件结尾，每次追加记录时覆盖
+const HAR_END: &[u8] = b"\n]}}\n";
+
+// HAR 1.2文件，每次写入后文件都是完整的，抓包中途结束也能打开
+#[derive(Debug)]
+pub struct HarWriter {
+    file: File,
+    // 已经写入的记录数
+    entries: usize,
+}
+
+impl HarWriter {
+    pub fn create(path: &str) -> io::Result<HarWriter> {
+        let mut file = File::create(path)?;
+        let creator = json!({
+            "name": env!("CARGO_PKG_NAME"),
+            "version": env!("CARGO_PKG_VERSION"),
+        });
+        write!(
+            file,
+            "{{\"log\":{{\"version\":\"1.2\",\"creator\":{creator},\"entries\":["
+        )?;
+        file.write_all(HAR_END)?;
+        Ok(HarWriter { file, entries: 0 })
+    }
+
+    // 追加一条记录，覆盖之前的结尾
+    pub fn write(&mut self, entry: &Value) -> io::Result<()> {
+        self.file.seek(SeekFrom::End(-(HAR_END.len() as i64)))?;
+        if self.entries > 0 {
+            self.file.write_all(b",")?;
+        }
+        self.file.write_all(b"\n")?;
+        serde_json::to_writer(&mut self.file, entry)?;
+        self.file.write_all(HAR_END)?;
+        self.file.flush()?;
+        self.entries += 1;
+        Ok(())
+    }
+}
+
+// 消息体，解码后的数据
+#[derive(Debug)]
+pub struct HarContent {
+    // 解码后的长度
+    pub size: usize,
+    // 传输的长度
+    pub body_size: usize,
+    pub mime_type: String,
+    pub text: String,
+    // text是base64编码的二进制数据
+    pub base64: bool,
+}
+
+impl HarContent {
+    // 二进制数据
+    pub fn binary(data: &[u8], body_size: usize, mime_type: String) -> Self {
+        HarContent {
+            size: data.len(),
+            body_size,
+            mime_type,
+            text: base64(data),
+            base64: true,
+        }
+    }
+}
+
+// 一次请求和响应
+// request、response: 用于输出的消息，可能已经脱敏
+// 没有请求时，不生成记录
+pub fn entry(
+    transaction: &HttpTransaction,
+    request: Option<(&HttpMessage, &HarContent)>,
+    response: Option<(&HttpMessage, &HarContent)>,
+) -> Option<Value> {
+    let (request, request_content) = request?;
+    let send = request.last_ts.saturating_sub(request.first_ts);
+    let wait = transaction.time_to_first_byte().unwrap_or_default();
+    let receive = response
+        .as_ref()
+        .map(|(response, _)| response.last_ts.saturating_sub(response.first_ts))
+        .unwrap_or_default();
+    let mut entry = json!({
+        "startedDateTime": date_time(request.first_ts),
+        "time": millis(send + wait + receive),
+        "request": request_json(&transaction.key, request, request_content),
+        "response": match response {
+            Some((response, content)) => response_json(response, content),
+            None => no_response_json(),
+        },
+        "cache": {},
+        "timings": {
+            "blocked": -1,
+            "dns": -1,
+            "connect": -1,
+            "send": millis(send),
+            "wait": millis(wait),
+            "receive": millis(receive),
+            "ssl": -1,
+        },
+        "serverIPAddress": transaction.key.dst.ip().to_string(),
+        "connection": transaction.key.src.to_string(),
+    });
+    if !request.complete {
+        entry["comment"] = json!("请求不完整");
+    }
+    Some(entry)
+}
+
+fn request_json(key: &StreamKey, request: &HttpMessage, content: &HarContent) -> Value {
+    let (method, version) = match &request.start_line {
+        StartLine::Request {
+            method, version, ..
+        } => (method.as_str(), version.as_str()),
+        StartLine::Response { version, .. } => ("", version.as_str()),
+    };
+    let url = url(Some(key), request);
+    let query = url.split_once('?').map(|(_, query)| query).unwrap_or("");
+    let query = query.split('#').next().unwrap_or(query);
+    let mut json = json!({
+        "method": method,
+        "url": url,
+        "httpVersion": version,
+        "cookies": request
+            .headers
+            .get_all("Cookie")
+            .flat_map(|cookie| cookie.split(';'))
+            .filter_map(|cookie| cookie_json(cookie, &[]))
+            .collect::<Vec<Value>>(),
+        "headers": headers_json(request),
+        "queryString": params_json(query),
+        "headersSize": request.head.len() + 4,
+        "bodySize": content.body_size,
+    });
+    if content.body_size > 0 {
+        let mut post_data = json!({
+            "mimeType": content.mime_type,
+            "text": content.text,
+        });
+        if content.base64 {
+            post_data["encoding"] = json!("base64");
+        } else if content
+            .mime_type
+            .to_ascii_lowercase()
+            .starts_with("application/x-www-form-urlencoded")
+        {
+            post_data["params"] = json!(params_json(&content.text));
+        }
+        json["postData"] = post_data;
+    }
+    json
+}
+
+fn response_json(response: &HttpMessage, content: &HarContent) -> Value {
+    let (version, status, reason) = match &response.start_line {
+        StartLine::Response {
+            version,
+            status,
+            reason,
+        } => (version.as_str(), *status, reason.as_str()),
+        StartLine::Request { version, .. } => (version.as_str(), 0, ""),
+    };
+    let mut content_json = json!({
+        "size": content.size,
+        "mimeType": content.mime_type,
+        "text": content.text,
+    });
+    if content.base64 {
+        content_json["encoding"] = json!("base64");
+    }
+    let mut json = json!({
+        "status": status,
+        "statusText": reason,
+        "httpVersion": version,
+        "cookies": response
+            .headers
+            .get_all("Set-Cookie")
+            .filter_map(|cookie| {
+                let mut parts = cookie.split(';');
+                cookie_json(parts.next()?, &parts.collect::<Vec<&str>>())
+            })
+            .collect::<Vec<Value>>(),
+        "headers": headers_json(response),
+        "content": content_json,
+        "redirectURL": response.headers.get("Location").unwrap_or_default(),
+        "headersSize": response.head.len() + 4,
+        "bodySize": content.body_size,
+    });
+    if !response.complete {
+        json["comment"] = json!("响应不完整");
+    }
+    json
+}
+
+// 没有收到响应
+fn no_response_json() -> Value {
+    json!({
+        "status": 0,
+        "statusText": "",
+        "httpVersion": "",
+        "cookies": [],
+        "headers": [],
+        "content": {"size": 0, "mimeType": "x-unknown"},
+        "redirectURL": "",
+        "headersSize": -1,
+        "bodySize": -1,
+        "comment": "没有响应",
+    })
+}
+
+// 完整的url，请求目标不是绝对形式时，用Host头或者目的地址补全
+// key: 请求方向，不按TCP流重组时没有
+pub fn url(key: Option<&StreamKey>, request: &HttpMessage) -> String {
+    let target = request.target().unwrap_or("/");
+    if target.contains("://") {
+        return target.to_string();
+    }
+    let host = request
+        .headers
+        .get("Host")
+        .map(String::from)
+        .or_else(|| key.map(|key| key.dst.to_string()))
+        .unwrap_or_else(|| "localhost".to_string());
+    if target.starts_with('/') {
+        format!("http://{host}{target}")
+    } else if target == "*" {
+        format!("http://{host}")
+    } else {
+        // CONNECT的请求目标是 主机:端口
+        format!("http://{target}")
+    }
+}
+
+pub fn headers_json(message: &HttpMessage) -> Vec<Value> {
+    message
+        .headers
+        .iter()
+        .map(|(name, value)| json!({"name": name, "value": value}))
+        .collect()
+}
+
+// 查询参数、表单字段，解码后输出
+fn params_json(query: &str) -> Vec<Value> {
+    query
+        .split('&')
+        .filter(|param| !param.is_empty())
+        .map(|param| {
+            let (name, value) = param.split_once('=').unwrap_or((param, ""));
+            json!({"name": pretty::url_decode(name), "value": pretty::url_decode(value)})
+        })
+        .collect()
+}
+
+// cookie，attributes是Set-Cookie中的属性
+fn cookie_json(cookie: &str, attributes: &[&str]) -> Option<Value> {
+    let (name, value) = cookie.trim().split_once('=')?;
+    let mut json = json!({"name": name.trim(), "value": value.trim()});
+    for attribute in attributes {
+        let (key, value) = attribute.split_once('=').unwrap_or((attribute, ""));
+        match key.trim().to_ascii_lowercase().as_str() {
+            "path" => json["path"] = json!(value.trim()),
+            "domain" => json["domain"] = json!(value.trim()),
+            "httponly" => json["httpOnly"] = json!(true),
+            "secure" => json["secure"] = json!(true),
+            _ => {}
+        }
+    }
+    Some(json)
+}
+
+// ISO 8601，本地时区，精确到毫秒
+fn date_time(ts: Duration) -> String {
+    local_time(ts).to_rfc3339_opts(SecondsFormat::Millis, false)
+}
+
+// 抓包时间转为本地时区
+pub fn local_time(ts: Duration) -> DateTime<Local> {
+    DateTime::from_timestamp(ts.as_secs() as i64, ts.subsec_nanos())
+        .unwrap_or_default()
+        .with_timezone(&Local)
+}
+
+pub fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text_content(text: &str) -> HarContent {
+        HarContent {
+            size: text.len(),
+            body_size: text.len(),
+            mime_type: "text/plain".to_string(),
+            text: text.to_string(),
+            base64: false,
+        }
+    }
+
+    fn read(path: &std::path::Path) -> Value {
+        serde_json::from_slice(&std::fs::read(path).unwrap()).unwrap()
+    }
+
+    // HAR 1.2中必须有的字段
+    fn check_entry(entry: &Value) {
+        for field in [
+            "startedDateTime",
+            "time",
+            "request",
+            "response",
+            "cache",
+            "timings",
+        ] {
+            assert!(!entry[field].is_null(), "{field}");
+        }
+        for field in [
+            "method",
+            "url",
+            "httpVersion",
+            "cookies",
+            "headers",
+            "queryString",
+            "headersSize",
+            "bodySize",
+        ] {
+            assert!(!entry["request"][field].is_null(), "request.{field}");
+        }
+        for field in [
+            "status",
+            "statusText",
+            "httpVersion",
+            "cookies",
+            "headers",
+            "content",
+            "redirectURL",
+            "headersSize",
+            "bodySize",
+        ] {
+            assert!(!entry["response"][field].is_null(), "response.{field}");
+        }
+        for field in ["send", "wait", "receive"] {
+            assert!(entry["timings"][field].is_number(), "timings.{field}");
+        }
+        assert!(entry["response"]["content"]["size"].is_number());
+        assert!(entry["response"]["content"]["mimeType"].is_string());
+    }
+
+    #[test]
+    fn writer_keeps_valid_har() {
+        let path = std::env::temp_dir().join(format!("http_dump_har_{}.har", std::process::id()));
+        let mut writer = HarWriter::create(path.to_str().unwrap()).unwrap();
+        let har = read(&path);
+        assert_eq!(har["log"]["version"], "1.2");
+        assert_eq!(har["log"]["creator"]["name"], env!("CARGO_PKG_NAME"));
+        assert_eq!(har["log"]["entries"], json!([]));
+
+        let mut request =
+            HttpMessage::parse(b"GET /a?x=1 HTTP/1.1\r\nHost: example.com\r\nCookie: c=1\r\n\r\n")
+                .unwrap();
+        request.first_ts = Duration::from_secs(1);
+        request.last_ts = Duration::from_secs(1);
+        let mut response =
+            HttpMessage::parse(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok").unwrap();
+        response.first_ts = Duration::from_millis(1100);
+        response.last_ts = Duration::from_millis(1150);
+        let transaction = HttpTransaction {
+            key: StreamKey {
+                src: ([10, 0, 0, 1], 5000).into(),
+                dst: ([10, 0, 0, 2], 80).into(),
+            },
+            request: Some(request),
+            response: Some(response),
+            replayed: None,
+        };
+        let (request, response) = (
+            transaction.request.as_ref().unwrap(),
+            transaction.response.as_ref().unwrap(),
+        );
+        let (empty, ok) = (text_content(""), text_content("ok"));
+        let entries = [
+            entry(&transaction, Some((request, &empty)), Some((response, &ok))).unwrap(),
+            entry(&transaction, Some((request, &empty)), None).unwrap(),
+        ];
+        for (index, entry) in entries.iter().enumerate() {
+            writer.write(entry).unwrap();
+            let har = read(&path);
+            let written = har["log"]["entries"].as_array().unwrap();
+            assert_eq!(written.len(), index + 1);
+            assert_eq!(written[index], *entry);
+            check_entry(&written[index]);
+        }
+
+        let har = read(&path);
+        let entry = &har["log"]["entries"][0];
+        assert_eq!(entry["request"]["url"], "http://example.com/a?x=1");
+        assert_eq!(entry["request"]["queryString"][0]["name"], "x");
+        assert_eq!(entry["request"]["cookies"][0]["value"], "1");
+        assert_eq!(entry["response"]["status"], 200);
+        assert_eq!(entry["response"]["content"]["text"], "ok");
+        assert_eq!(entry["time"], 150.0);
+        assert_eq!(har["log"]["entries"][1]["response"]["status"], 0);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,173 @@
+use serde_json::{json, Value};
+
+use super::{
+    har::{self, HarContent},
+    message::{HttpMessage, StartLine},
+    transaction::HttpTransaction,
+};
+
+// 一条http消息，json输出时每条消息一行
+// message: 用于输出的消息，可能已经脱敏，不含消息体，body_size: 传输的消息体长度
+// content: 消息体，不显示时没有，request: 配对的请求，用于生成响应的url
+pub fn message(
+    transaction: &HttpTransaction,
+    message: &HttpMessage,
+    body_size: usize,
+    content: Option<&HarContent>,
+    request: Option<&HttpMessage>,
+) -> Value {
+    // 响应和请求方向相反
+    let key = match message.start_line {
+        StartLine::Request { .. } => transaction.key,
+        StartLine::Response { .. } => transaction.key.reverse(),
+    };
+    let mut json = json!({
+        "ts": message.first_ts.as_secs_f64(),
+        "src": key.src.to_string(),
+        "dst": key.dst.to_string(),
+        "protocol": "HTTP",
+    });
+    match &message.start_line {
+        StartLine::Request {
+            method,
+            target,
+            version,
+        } => {
+            json["type"] = json!("request");
+            json["method"] = json!(method);
+            json["target"] = json!(target);
+            json["url"] = json!(har::url(Some(&key), message));
+            json["version"] = json!(version);
+        }
+        StartLine::Response {
+            version,
+            status,
+            reason,
+        } => {
+            json["type"] = json!("response");
+            json["version"] = json!(version);
+            json["status"] = json!(status);
+            json["reason"] = json!(reason);
+            if let Some(request) = request {
+                json["method"] = json!(request.method());
+                json["url"] = json!(har::url(Some(&transaction.key), request));
+            }
+            if let Some(duration) = transaction.duration() {
+                json["duration_ms"] = json!(har::millis(duration));
+            }
+        }
+    }
+    json["complete"] = json!(message.complete);
+    json["headers"] = json!(har::headers_json(message));
+    json["body_size"] = json!(body_size);
+    if let Some(content) = content.filter(|content| content.size > 0) {
+        json["body"] = json!(content.text);
+        if content.base64 {
+            json["body_encoding"] = json!("base64");
+        }
+        json["decoded_size"] = json!(content.size);
+    }
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{process::change_data::base64, stream::StreamKey};
+
+    use super::*;
+
+    fn transaction() -> HttpTransaction {
+        let mut request = HttpMessage::parse(
+            b"POST /up HTTP/1.1\r\nHost: example.com\r\nContent-Length: 3\r\n\r\nabc",
+        )
+        .unwrap();
+        request.first_ts = Duration::from_secs(1);
+        request.last_ts = Duration::from_secs(1);
+        let mut response = HttpMessage::parse(
+            b"HTTP/1.1 201 Created\r\nContent-Type: application/octet-stream\r\nContent-Length: 3\r\n\r\n\xff\x00\xfe",
+        )
+        .unwrap();
+        response.first_ts = Duration::from_millis(1200);
+        response.last_ts = Duration::from_millis(1250);
+        HttpTransaction {
+            key: StreamKey {
+                src: ([10, 0, 0, 1], 5000).into(),
+                dst: ([10, 0, 0, 2], 80).into(),
+            },
+            request: Some(request),
+            response: Some(response),
+            replayed: None,
+        }
+    }
+
+    // 输出时每条消息一行，必须能解析回同样的json
+    fn line(json: &Value) -> Value {
+        let line = json.to_string();
+        assert!(!line.contains('\n'));
+        serde_json::from_str(&line).unwrap()
+    }
+
+    #[test]
+    fn request() {
+        let transaction = transaction();
+        let request = transaction.request.as_ref().unwrap();
+        let content = HarContent {
+            size: 3,
+            body_size: 3,
+            mime_type: "text/plain".to_string(),
+            text: "abc".to_string(),
+            base64: false,
+        };
+        let json = line(&message(&transaction, request, 3, Some(&content), None));
+        assert_eq!(json["type"], "request");
+        assert_eq!(json["ts"], 1.0);
+        assert_eq!(json["src"], "10.0.0.1:5000");
+        assert_eq!(json["dst"], "10.0.0.2:80");
+        assert_eq!(json["protocol"], "HTTP");
+        assert_eq!(json["method"], "POST");
+        assert_eq!(json["target"], "/up");
+        assert_eq!(json["url"], "http://example.com/up");
+        assert_eq!(json["version"], "HTTP/1.1");
+        assert_eq!(json["complete"], true);
+        assert_eq!(json["body_size"], 3);
+        assert_eq!(json["body"], "abc");
+        assert!(json.get("body_encoding").is_none());
+        assert!(json.get("status").is_none());
+    }
+
+    #[test]
+    fn binary_response() {
+        let transaction = transaction();
+        let (request, response) = (
+            transaction.request.as_ref().unwrap(),
+            transaction.response.as_ref().unwrap(),
+        );
+        let body = [0xff, 0x00, 0xfe];
+        let content = HarContent::binary(&body, 3, "application/octet-stream".to_string());
+        let json = line(&message(
+            &transaction,
+            response,
+            3,
+            Some(&content),
+            Some(request),
+        ));
+        assert_eq!(json["type"], "response");
+        assert_eq!(json["src"], "10.0.0.2:80");
+        assert_eq!(json["dst"], "10.0.0.1:5000");
+        assert_eq!(json["status"], 201);
+        assert_eq!(json["reason"], "Created");
+        assert_eq!(json["method"], "POST");
+        assert_eq!(json["url"], "http://example.com/up");
+        assert_eq!(json["duration_ms"], 250.0);
+        assert_eq!(json["body"], base64(&body));
+        assert_eq!(json["body_encoding"], "base64");
+        assert_eq!(json["decoded_size"], 3);
+
+        // 不显示消息体时只有长度
+        let json = line(&message(&transaction, response, 3, None, Some(request)));
+        assert_eq!(json["body_size"], 3);
+        assert!(json.get("body").is_none());
+    }
+}
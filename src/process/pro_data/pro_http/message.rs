@@ -0,0 +1,755 @@
+use std::{cell::OnceCell, collections::VecDeque, fmt, time::Duration};
+
+use super::chunked;
+
+// 请求头、响应头最大长度，超过后认为不是http数据
+const MAX_HEAD_LEN: usize = 64 * 1024;
+// 请求体、响应体最多保存的长度，超过的部分丢弃，但是仍然按照长度解析
+const MAX_BODY_LEN: usize = 32 * 1024 * 1024;
+
+// 首行
+#[derive(Debug, Clone)]
+pub enum StartLine {
+    // 请求行：方法 目标 版本
+    Request {
+        method: String,
+        target: String,
+        version: String,
+    },
+    // 状态行：版本 状态码 原因
+    Response {
+        version: String,
+        status: u16,
+        reason: String,
+    },
+}
+
+impl StartLine {
+    fn parse(line: &str) -> Option<StartLine> {
+        let mut parts = line.splitn(3, ' ');
+        let first = parts.next()?;
+        let second = parts.next()?;
+        let third = parts.next().unwrap_or("");
+        if first.starts_with("HTTP/") {
+            return Some(StartLine::Response {
+                version: first.to_string(),
+                status: second.parse().ok()?,
+                reason: third.to_string(),
+            });
+        }
+        if first.is_empty() || !first.bytes().all(|b| b.is_ascii_alphabetic()) {
+            return None;
+        }
+        if !third.starts_with("HTTP/") {
+            return None;
+        }
+        Some(StartLine::Request {
+            method: first.to_string(),
+            target: second.to_string(),
+            version: third.to_string(),
+        })
+    }
+}
+
+impl fmt::Display for StartLine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StartLine::Request {
+                method,
+                target,
+                version,
+            } => write!(f, "{method} {target} {version}"),
+            StartLine::Response {
+                version,
+                status,
+                reason,
+            } => write!(f, "{version} {status} {reason}"),
+        }
+    }
+}
+
+// http头，保留顺序和原始大小写，查找时忽略大小写
+#[derive(Debug, Clone, Default)]
+pub struct HttpHeaders(Vec<(String, String)>);
+
+impl HttpHeaders {
+    // 第一个同名的头
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    // 所有同名的头
+    pub fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.0
+            .iter()
+            .filter(move |(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    // 逗号分隔的值，比如 Transfer-Encoding: gzip, chunked，多个同名头合并
+    pub fn get_list<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.get_all(name)
+            .flat_map(|v| v.split(','))
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+    }
+
+    // 所有的头，按原始顺序
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+}
+
+// http消息，请求或者响应
+#[derive(Debug, Clone)]
+pub struct HttpMessage {
+    pub start_line: StartLine,
+    pub headers: HttpHeaders,
+    // 原始的首行和头，不包含最后的空行
+    pub head: Vec<u8>,
+    // 原始的消息体，分块传输时，包含分块格式
+    pub body: Vec<u8>,
+    // 消息是否完整，连接结束时还没有收完，或者消息体超过限制时为false
+    pub complete: bool,
+    // 第一个字节、最后一个字节到达时的抓包时间，不按TCP流重组时为0
+    pub first_ts: Duration,
+    pub last_ts: Duration,
+    // 解码后的消息体，第一次使用时解码，之后过滤、输出、导出共用
+    pub decoded: OnceCell<DecodedBody>,
+}
+
+// 处理分块传输、解压后的消息体
+#[derive(Debug, Clone)]
+pub struct DecodedBody {
+    // 解码后的数据，和原始消息体相同时没有
+    pub data: Option<Vec<u8>>,
+    // 分块传输的尾部字段
+    pub trailer: Vec<u8>,
+    // 解码过程中的说明
+    pub notes: Vec<String>,
+}
+
+impl HttpMessage {
+    // 单个报文按照一条消息解析，找不到空行时，都认为是头
+    // 不按TCP流重组时使用
+    pub fn parse(data: &[u8]) -> Option<HttpMessage> {
+        let (head, body) = match find_head_end(data) {
+            Some((head_len, body_start)) => (&data[..head_len], &data[body_start..]),
+            None => (data, &[] as &[u8]),
+        };
+        let mut message = parse_head(head)?;
+        message.body = body.to_vec();
+        Some(message)
+    }
+
+    // 状态码，请求时为None
+    pub fn status(&self) -> Option<u16> {
+        match &self.start_line {
+            StartLine::Request { .. } => None,
+            StartLine::Response { status, .. } => Some(*status),
+        }
+    }
+
+    // 请求方法，响应时为None
+    pub fn method(&self) -> Option<&str> {
+        match &self.start_line {
+            StartLine::Request { method, .. } => Some(method),
+            StartLine::Response { .. } => None,
+        }
+    }
+
+    // 请求目标，响应时为None
+    pub fn target(&self) -> Option<&str> {
+        match &self.start_line {
+            StartLine::Request { target, .. } => Some(target),
+            StartLine::Response { .. } => None,
+        }
+    }
+
+    // 主机，优先使用绝对形式的请求目标，其次是Host头，不含端口
+    pub fn host(&self) -> Option<&str> {
+        let authority = match self.target().and_then(split_absolute) {
+            Some((authority, _)) => authority,
+            None => self.headers.get("Host")?,
+        };
+        // IPv6地址带有中括号，[::1]:8080
+        let host = match authority.strip_prefix('[') {
+            Some(rest) => rest.split(']').next().unwrap_or(rest),
+            None => authority.split(':').next().unwrap_or(authority),
+        };
+        Some(host)
+    }
+
+    // 请求路径，不含查询参数
+    pub fn path(&self) -> Option<&str> {
+        let target = self.target()?;
+        let target = match split_absolute(target) {
+            Some((_, path)) => path,
+            None => target,
+        };
+        Some(target.split(['?', '#']).next().unwrap_or(target))
+    }
+
+    // 分块传输，Transfer-Encoding最后一个是chunked
+    pub fn is_chunked(&self) -> bool {
+        self.headers
+            .get_list("Transfer-Encoding")
+            .last()
+            .is_some_and(|v| v.eq_ignore_ascii_case("chunked"))
+    }
+
+    // 保存消息体，超过限制的部分丢弃
+    fn append_body(&mut self, data: &[u8]) {
+        let len = data.len().min(MAX_BODY_LEN.saturating_sub(self.body.len()));
+        if len < data.len() {
+            self.complete = false;
+        }
+        self.body.extend_from_slice(&data[..len]);
+    }
+}
+
+// 绝对形式的请求目标，http://host:port/path?query，返回主机和路径
+fn split_absolute(target: &str) -> Option<(&str, &str)> {
+    let (_, rest) = target.split_once("://")?;
+    match rest.find(['/', '?', '#']) {
+        Some(index) => Some((&rest[..index], &rest[index..])),
+        None => Some((rest, "/")),
+    }
+}
+
+// 查找头结束的位置，返回头长度、消息体开始位置
+// 兼容只用\n换行的情况
+pub(super) fn find_head_end(data: &[u8]) -> Option<(usize, usize)> {
+    let mut index = 0;
+    while let Some(pos) = data[index..].iter().position(|b| *b == b'\n') {
+        let line_end = index + pos;
+        let next = line_end + 1;
+        let blank_len = if data[next..].starts_with(b"\r\n") {
+            2
+        } else if data[next..].starts_with(b"\n") {
+            1
+        } else {
+            0
+        };
+        if blank_len > 0 {
+            let head_len = if line_end > 0 && data[line_end - 1] == b'\r' {
+                line_end - 1
+            } else {
+                line_end
+            };
+            return Some((head_len, next + blank_len));
+        }
+        index = next;
+    }
+    None
+}
+
+// 解析首行和头，head不包含最后的空行
+fn parse_head(head: &[u8]) -> Option<HttpMessage> {
+    let head_str = String::from_utf8_lossy(head);
+    let mut lines = head_str.lines();
+    let start_line = StartLine::parse(lines.next()?.trim_end())?;
+    let mut headers: Vec<(String, String)> = Vec::new();
+    for line in lines {
+        // 以空白开头的是上一个头的续行，已废弃，但是仍然兼容
+        if line.starts_with([' ', '\t']) {
+            if let Some((_, v)) = headers.last_mut() {
+                v.push(' ');
+                v.push_str(line.trim());
+            }
+            continue;
+        }
+        if let Some((k, v)) = line.split_once(':') {
+            headers.push((k.trim().to_string(), v.trim().to_string()));
+        }
+    }
+    Some(HttpMessage {
+        start_line,
+        headers: HttpHeaders(headers),
+        head: head.to_vec(),
+        body: Vec::new(),
+        complete: true,
+        first_ts: Duration::ZERO,
+        last_ts: Duration::ZERO,
+        decoded: OnceCell::new(),
+    })
+}
+
+// 消息体的长度确定方式
+#[derive(Debug)]
+enum Framing {
+    // 剩余的长度
+    Length(usize),
+    // 分块传输
+    Chunked(ChunkState),
+    // 直到连接关闭
+    Close,
+    // 协议升级，没有消息体，之后的数据不再是http
+    Upgrade,
+}
+
+// 分块传输解析状态
+#[derive(Debug)]
+enum ChunkState {
+    // 等待块大小行
+    Size,
+    // 块数据，剩余的长度
+    Data(usize),
+    // 块数据之后的\r\n
+    DataEnd,
+    // 尾部头，直到空行
+    Trailer,
+}
+
+// 解析状态
+#[derive(Debug)]
+enum ParseState {
+    // 等待首行和头
+    Head,
+    // 消息体
+    Body(Box<HttpMessage>, Framing),
+    // 协议升级（101、CONNECT），之后的数据不再是http
+    Upgraded,
+}
+
+// 单个方向上的http消息解析
+#[derive(Debug)]
+pub struct MessageParser {
+    // 还没有解析的数据
+    buf: Vec<u8>,
+    // buf中第一个字节到达时的抓包时间
+    buf_ts: Duration,
+    state: ParseState,
+}
+
+impl MessageParser {
+    pub fn new() -> Self {
+        MessageParser {
+            buf: Vec::new(),
+            buf_ts: Duration::ZERO,
+            state: ParseState::Head,
+        }
+    }
+
+    // 协议升级，之后的数据都忽略
+    pub fn upgrade(&mut self) {
+        self.buf.clear();
+        self.state = ParseState::Upgraded;
+    }
+
+    pub fn is_upgraded(&self) -> bool {
+        matches!(self.state, ParseState::Upgraded)
+    }
+
+    // 追加按序到达的数据，返回解析完成的消息
+    // ts: 数据到达时的抓包时间
+    // methods: 同一连接上，还没有收到响应的请求方法，用于判断响应有没有消息体
+    pub fn push(
+        &mut self,
+        data: &[u8],
+        ts: Duration,
+        methods: &mut VecDeque<String>,
+    ) -> Vec<HttpMessage> {
+        let mut messages = Vec::new();
+        if let ParseState::Upgraded = self.state {
+            return messages;
+        }
+        if self.buf.is_empty() {
+            self.buf_ts = ts;
+        }
+        self.buf.extend_from_slice(data);
+        loop {
+            match std::mem::replace(&mut self.state, ParseState::Head) {
+                ParseState::Head => match self.parse_head(methods) {
+                    Some(ParseState::Body(mut message, framing)) => {
+                        message.first_ts = self.buf_ts;
+                        message.last_ts = ts;
+                        // 同一批数据中，后面的消息也是这时到达的
+                        self.buf_ts = ts;
+                        self.state = ParseState::Body(message, framing);
+                    }
+                    Some(state) => self.state = state,
+                    None => break,
+                },
+                ParseState::Body(message, Framing::Upgrade) => {
+                    messages.push(*message);
+                    self.upgrade();
+                    break;
+                }
+                ParseState::Body(mut message, framing) => {
+                    if !self.buf.is_empty() {
+                        message.last_ts = ts;
+                    }
+                    match self.parse_body(&mut message, framing) {
+                        Some(framing) => {
+                            self.state = ParseState::Body(message, framing);
+                            break;
+                        }
+                        None => messages.push(*message),
+                    }
+                }
+                ParseState::Upgraded => {
+                    self.upgrade();
+                    break;
+                }
+            }
+        }
+        messages
+    }
+
+    // 连接结束，返回还没有完成的消息
+    // 直到连接关闭的消息体，这时才完整
+    pub fn close(&mut self) -> Option<HttpMessage> {
+        let state = std::mem::replace(&mut self.state, ParseState::Head);
+        self.buf.clear();
+        match state {
+            ParseState::Body(message, Framing::Close) => Some(*message),
+            ParseState::Body(mut message, _) => {
+                message.complete = false;
+                Some(*message)
+            }
+            _ => None,
+        }
+    }
+
+    // 解析首行和头，数据不够时返回None
+    fn parse_head(&mut self, methods: &mut VecDeque<String>) -> Option<ParseState> {
+        // 消息之间可能有多余的空行
+        let blank = self
+            .buf
+            .iter()
+            .take_while(|b| **b == b'\r' || **b == b'\n')
+            .count();
+        self.buf.drain(..blank);
+        if self.buf.is_empty() {
+            return None;
+        }
+        let Some((head_len, body_start)) = find_head_end(&self.buf) else {
+            if self.buf.len() > MAX_HEAD_LEN {
+                // 不是http数据
+                self.buf.clear();
+            }
+            return None;
+        };
+        let message = parse_head(&self.buf[..head_len]);
+        self.buf.drain(..body_start);
+        let Some(message) = message else {
+            // 首行不对，丢弃这个头，等待下一条消息
+            return Some(ParseState::Head);
+        };
+        let framing = match &message.start_line {
+            StartLine::Request { method, .. } => {
+                methods.push_back(method.clone());
+                request_framing(&message)
+            }
+            StartLine::Response { status, .. } => {
+                let status = *status;
+                // 1xx是临时响应，后面还有最终响应
+                let method = if (100..200).contains(&status) && status != 101 {
+                    None
+                } else {
+                    methods.pop_front()
+                };
+                let connect = method.as_deref() == Some("CONNECT") && (200..300).contains(&status);
+                if status == 101 || connect {
+                    return Some(ParseState::Body(Box::new(message), Framing::Upgrade));
+                }
+                response_framing(&message, method.as_deref())
+            }
+        };
+        Some(ParseState::Body(Box::new(message), framing))
+    }
+
+    // 解析消息体，消息体完整时返回None
+    fn parse_body(&mut self, message: &mut HttpMessage, framing: Framing) -> Option<Framing> {
+        match framing {
+            Framing::Length(remaining) => {
+                let len = remaining.min(self.buf.len());
+                message.append_body(&self.buf[..len]);
+                self.buf.drain(..len);
+                if remaining == len {
+                    None
+                } else {
+                    Some(Framing::Length(remaining - len))
+                }
+            }
+            Framing::Close => {
+                message.append_body(&self.buf);
+                self.buf.clear();
+                Some(Framing::Close)
+            }
+            Framing::Chunked(state) => self.parse_chunked(message, state).map(Framing::Chunked),
+            Framing::Upgrade => None,
+        }
+    }
+
+    // 解析分块传输的消息体，保留原始的分块格式，完整时返回None
+    // 这里只确定消息在流中的结束位置，数据可能分多次到达，所以用状态机逐步解析
+    // 合并分块、取尾部字段在输出时由chunked::decode处理，按单个报文解析时也会用到
+    fn parse_chunked(
+        &mut self,
+        message: &mut HttpMessage,
+        mut state: ChunkState,
+    ) -> Option<ChunkState> {
+        loop {
+            match state {
+                ChunkState::Size | ChunkState::Trailer => {
+                    let Some(pos) = self.buf.iter().position(|b| *b == b'\n') else {
+                        return Some(state);
+                    };
+                    let line = self.buf[..pos].trim_ascii();
+                    state = match state {
+                        // 块大小和解码时的解析方式相同，忽略扩展
+                        ChunkState::Size => match chunked::chunk_size(line) {
+                            Some(0) => ChunkState::Trailer,
+                            Some(size) => ChunkState::Data(size),
+                            None => {
+                                // 格式错误，无法继续解析
+                                message.append_body(&self.buf[..pos + 1]);
+                                message.complete = false;
+                                self.buf.clear();
+                                return None;
+                            }
+                        },
+                        _ if line.is_empty() => {
+                            message.append_body(&self.buf[..pos + 1]);
+                            self.buf.drain(..pos + 1);
+                            return None;
+                        }
+                        _ => ChunkState::Trailer,
+                    };
+                    message.append_body(&self.buf[..pos + 1]);
+                    self.buf.drain(..pos + 1);
+                }
+                ChunkState::Data(remaining) => {
+                    let len = remaining.min(self.buf.len());
+                    message.append_body(&self.buf[..len]);
+                    self.buf.drain(..len);
+                    if len < remaining {
+                        return Some(ChunkState::Data(remaining - len));
+                    }
+                    state = ChunkState::DataEnd;
+                }
+                ChunkState::DataEnd => {
+                    let Some(pos) = self.buf.iter().position(|b| *b == b'\n') else {
+                        return Some(state);
+                    };
+                    message.append_body(&self.buf[..pos + 1]);
+                    self.buf.drain(..pos + 1);
+                    state = ChunkState::Size;
+                }
+            }
+        }
+    }
+}
+
+// 请求的消息体长度：分块传输、Content-Length，都没有时没有消息体
+fn request_framing(message: &HttpMessage) -> Framing {
+    if message.is_chunked() {
+        return Framing::Chunked(ChunkState::Size);
+    }
+    Framing::Length(content_length(message).unwrap_or(0))
+}
+
+// 响应的消息体长度
+// HEAD请求的响应、1xx、204、304没有消息体
+fn response_framing(message: &HttpMessage, method: Option<&str>) -> Framing {
+    let status = message.status().unwrap_or(0);
+    if method == Some("HEAD") || (100..200).contains(&status) || status == 204 || status == 304 {
+        return Framing::Length(0);
+    }
+    if message.is_chunked() {
+        return Framing::Chunked(ChunkState::Size);
+    }
+    if message.headers.get("Transfer-Encoding").is_some() {
+        // 有其他传输编码，但最后一个不是chunked，直到连接关闭
+        return Framing::Close;
+    }
+    match content_length(message) {
+        Some(len) => Framing::Length(len),
+        None => Framing::Close,
+    }
+}
+
+// Content-Length，多个值不一致时认为无效
+fn content_length(message: &HttpMessage) -> Option<usize> {
+    let mut lens = message
+        .headers
+        .get_list("Content-Length")
+        .map(|v| v.parse::<usize>().ok());
+    let len = lens.next()??;
+    if lens.all(|v| v == Some(len)) {
+        Some(len)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 按不同的长度切分后逐段解析，结果应该一样
+    // 返回每种切分下解析出的消息，以及连接关闭时未完成的消息
+    fn parse(data: &[u8], methods: &[&str]) -> Vec<(Vec<HttpMessage>, MessageParser)> {
+        [1, 3, 7, 64, data.len()]
+            .into_iter()
+            .map(|size| {
+                let mut parser = MessageParser::new();
+                let mut methods = methods.iter().map(|m| m.to_string()).collect();
+                let mut messages = Vec::new();
+                for (index, chunk) in data.chunks(size).enumerate() {
+                    let ts = Duration::from_millis(index as u64);
+                    messages.extend(parser.push(chunk, ts, &mut methods));
+                }
+                (messages, parser)
+            })
+            .collect()
+    }
+
+    fn summary(message: &HttpMessage) -> (String, Vec<u8>, bool) {
+        (
+            message.start_line.to_string(),
+            message.body.clone(),
+            message.complete,
+        )
+    }
+
+    fn summaries(messages: &[HttpMessage]) -> Vec<(String, Vec<u8>, bool)> {
+        messages.iter().map(summary).collect()
+    }
+
+    fn expected(items: &[(&str, &[u8])]) -> Vec<(String, Vec<u8>, bool)> {
+        items
+            .iter()
+            .map(|(line, body)| (line.to_string(), body.to_vec(), true))
+            .collect()
+    }
+
+    #[test]
+    fn no_body_responses() {
+        let data = b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\n\
+            HTTP/1.1 100 Continue\r\n\r\n\
+            HTTP/1.1 204 No Content\r\nContent-Length: 3\r\n\r\n\
+            HTTP/1.1 304 Not Modified\r\nContent-Length: 3\r\n\r\n\
+            HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok";
+        for (messages, mut parser) in parse(data, &["HEAD", "POST", "GET", "GET"]) {
+            assert_eq!(
+                summaries(&messages),
+                expected(&[
+                    ("HTTP/1.1 200 OK", b""),
+                    ("HTTP/1.1 100 Continue", b""),
+                    ("HTTP/1.1 204 No Content", b""),
+                    ("HTTP/1.1 304 Not Modified", b""),
+                    ("HTTP/1.1 200 OK", b"ok"),
+                ])
+            );
+            assert!(parser.close().is_none());
+        }
+    }
+
+    #[test]
+    fn pipelined_requests() {
+        let data = b"POST /a HTTP/1.1\r\nContent-Length: 5\r\n\r\nhello\
+            GET /b HTTP/1.1\r\n\r\n\r\n\
+            PUT /c HTTP/1.1\nContent-Length: 3\n\nxyz";
+        for (messages, _) in parse(data, &[]) {
+            assert_eq!(
+                summaries(&messages),
+                expected(&[
+                    ("POST /a HTTP/1.1", b"hello"),
+                    ("GET /b HTTP/1.1", b""),
+                    ("PUT /c HTTP/1.1", b"xyz"),
+                ])
+            );
+        }
+        // 请求方法按顺序记录，用于响应配对
+        let mut methods = VecDeque::new();
+        MessageParser::new().push(data, Duration::ZERO, &mut methods);
+        assert_eq!(methods, ["POST", "GET", "PUT"]);
+    }
+
+    #[test]
+    fn chunked_with_trailers() {
+        let body: &[u8] = b"5;ext=1\r\nhello\r\n6\r\n world\r\n0\r\nX-Sum: 1\r\nX-End: 2\r\n\r\n";
+        let data = [
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n".as_slice(),
+            body,
+            b"HTTP/1.1 200 OK\r\nContent-Length: 1\r\n\r\n!",
+        ]
+        .concat();
+        for (messages, _) in parse(&data, &["GET", "GET"]) {
+            assert_eq!(
+                summaries(&messages),
+                expected(&[("HTTP/1.1 200 OK", body), ("HTTP/1.1 200 OK", b"!")])
+            );
+        }
+    }
+
+    #[test]
+    fn upgrade() {
+        let data = b"HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\n\r\n\x81\x05hello";
+        for (messages, mut parser) in parse(data, &["GET"]) {
+            assert_eq!(
+                summaries(&messages),
+                expected(&[("HTTP/1.1 101 Switching Protocols", b"")])
+            );
+            assert!(parser.is_upgraded());
+            assert!(parser.close().is_none());
+        }
+
+        let data = b"HTTP/1.1 200 Connection established\r\n\r\n\x16\x03\x01\x00\x05hello";
+        for (messages, parser) in parse(data, &["CONNECT"]) {
+            assert_eq!(
+                summaries(&messages),
+                expected(&[("HTTP/1.1 200 Connection established", b"")])
+            );
+            assert!(parser.is_upgraded());
+        }
+    }
+
+    #[test]
+    fn close_framing() {
+        let data = b"HTTP/1.0 200 OK\r\nContent-Type: text/plain\r\n\r\nuntil close";
+        for (messages, mut parser) in parse(data, &["GET"]) {
+            assert!(messages.is_empty());
+            let message = parser.close().unwrap();
+            assert_eq!(
+                summary(&message),
+                ("HTTP/1.0 200 OK".to_string(), b"until close".to_vec(), true)
+            );
+        }
+    }
+
+    #[test]
+    fn incomplete_on_close() {
+        let data = b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nhalf";
+        for (messages, mut parser) in parse(data, &["GET"]) {
+            assert!(messages.is_empty());
+            let message = parser.close().unwrap();
+            assert_eq!(
+                summary(&message),
+                ("HTTP/1.1 200 OK".to_string(), b"half".to_vec(), false)
+            );
+        }
+    }
+
+    #[test]
+    fn message_timestamps() {
+        let mut parser = MessageParser::new();
+        let mut methods = VecDeque::new();
+        let first = parser.push(
+            b"POST / HTTP/1.1\r\nContent-Length: 2\r\n\r\n",
+            Duration::from_secs(1),
+            &mut methods,
+        );
+        assert!(first.is_empty());
+        let messages = parser.push(b"ok", Duration::from_secs(2), &mut methods);
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].first_ts, Duration::from_secs(1));
+        assert_eq!(messages[0].last_ts, Duration::from_secs(2));
+    }
+}
@@ -0,0 +1,281 @@
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::PathBuf,
+    time::Duration,
+};
+
+use super::{binary, har, message::HttpMessage, pretty, transaction::HttpTransaction};
+
+// 索引文件名
+const INDEX_FILE_NAME: &str = "index.tsv";
+// 文件名最大长度，不含序号和扩展名
+const MAX_NAME_LEN: usize = 100;
+// url中的扩展名最大长度，超过时根据Content-Type确定
+const MAX_EXTENSION_LEN: usize = 8;
+// Content-Type对应的扩展名
+const EXTENSIONS: [(&str, &str); 26] = [
+    ("text/html", "html"),
+    ("text/plain", "txt"),
+    ("text/css", "css"),
+    ("text/csv", "csv"),
+    ("text/xml", "xml"),
+    ("text/javascript", "js"),
+    ("application/javascript", "js"),
+    ("application/json", "json"),
+    ("application/xml", "xml"),
+    ("application/x-www-form-urlencoded", "txt"),
+    ("application/pdf", "pdf"),
+    ("application/zip", "zip"),
+    ("application/gzip", "gz"),
+    ("application/wasm", "wasm"),
+    ("application/octet-stream", "bin"),
+    ("image/png", "png"),
+    ("image/jpeg", "jpg"),
+    ("image/gif", "gif"),
+    ("image/webp", "webp"),
+    ("image/svg+xml", "svg"),
+    ("image/x-icon", "ico"),
+    ("image/vnd.microsoft.icon", "ico"),
+    ("font/woff", "woff"),
+    ("font/woff2", "woff2"),
+    ("audio/mpeg", "mp3"),
+    ("video/mp4", "mp4"),
+];
+
+// 导出消息体到目录，和Wireshark的导出对象相同
+// 文件名取自url路径，重名时加序号，目录下的index.tsv记录文件和请求的对应关系
+#[derive(Debug)]
+pub struct ObjectExport {
+    dir: PathBuf,
+    index: File,
+}
+
+impl ObjectExport {
+    pub fn create(dir: &str) -> io::Result<ObjectExport> {
+        let dir = PathBuf::from(dir);
+        fs::create_dir_all(&dir)?;
+        let mut index = File::create(dir.join(INDEX_FILE_NAME))?;
+        index.write_all(
+            "文件\t类型\t方法\tURL\t状态码\tContent-Type\t大小\t客户端\t服务端\t时间\n".as_bytes(),
+        )?;
+        Ok(ObjectExport { dir, index })
+    }
+
+    // 保存解码后的消息体，并写入索引
+    // message: 请求或者响应，data: 解码后的消息体
+    pub fn save(
+        &mut self,
+        transaction: &HttpTransaction,
+        message: &HttpMessage,
+        data: &[u8],
+    ) -> io::Result<()> {
+        let is_request = message.status().is_none();
+        let url = transaction
+            .request
+            .as_ref()
+            .map(|request| har::url(Some(&transaction.key), request))
+            .unwrap_or_default();
+        let content_type = message.headers.get("Content-Type").unwrap_or_default();
+        let name = self.file_name(&url, content_type, is_request);
+        fs::write(self.dir.join(&name), data)?;
+
+        let fields = [
+            name,
+            if is_request { "请求" } else { "响应" }.to_string(),
+            transaction
+                .request
+                .as_ref()
+                .and_then(HttpMessage::method)
+                .unwrap_or_default()
+                .to_string(),
+            url,
+            transaction
+                .response
+                .as_ref()
+                .and_then(HttpMessage::status)
+                .map(|status| status.to_string())
+                .unwrap_or_default(),
+            content_type.to_string(),
+            data.len().to_string(),
+            transaction.key.src.to_string(),
+            transaction.key.dst.to_string(),
+            date_time(message.first_ts),
+        ];
+        let line = fields
+            .iter()
+            // 字段中不能有分隔符
+            .map(|field| field.replace(['\t', '\r', '\n'], " "))
+            .collect::<Vec<String>>()
+            .join("\t");
+        writeln!(self.index, "{line}")?;
+        self.index.flush()
+    }
+
+    // 文件名，url路径的最后一段，没有扩展名时根据Content-Type补充
+    // 请求体加上.request，重名时加序号
+    fn file_name(&self, url: &str, content_type: &str, is_request: bool) -> String {
+        let path = url.split_once("://").map_or(url, |(_, rest)| {
+            rest.find('/').map_or("", |index| &rest[index..])
+        });
+        let path = path.split(['?', '#']).next().unwrap_or(path);
+        let last = pretty::url_decode(path.rsplit('/').next().unwrap_or_default());
+        // 只保留安全的字符，避免路径穿越
+        let last: String = last
+            .chars()
+            .map(|c| {
+                if c.is_alphanumeric() || matches!(c, '.' | '-' | '_') {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .take(MAX_NAME_LEN)
+            .collect();
+        let last = match last.trim_matches('.') {
+            "" if url.is_empty() => "object",
+            "" => "index",
+            last => last,
+        };
+        // 请求体的类型和url无关，扩展名根据Content-Type确定
+        let (stem, extension) = match last.rsplit_once('.') {
+            Some((stem, extension))
+                if !is_request
+                    && extension.len() <= MAX_EXTENSION_LEN
+                    && extension.chars().all(|c| c.is_ascii_alphanumeric()) =>
+            {
+                (stem, extension.to_string())
+            }
+            _ => (last, extension(content_type).to_string()),
+        };
+        let extension = if is_request {
+            format!("request.{extension}")
+        } else {
+            extension
+        };
+        let mut name = format!("{stem}.{extension}");
+        let mut number = 0;
+        // 目录中已经有的文件不覆盖
+        while self.dir.join(&name).exists() {
+            number += 1;
+            name = format!("{stem}-{number}.{extension}");
+        }
+        name
+    }
+}
+
+// 根据Content-Type获取扩展名
+fn extension(content_type: &str) -> &'static str {
+    let mime = binary::mime(Some(content_type));
+    if let Some((_, extension)) = EXTENSIONS.iter().find(|(name, _)| *name == mime) {
+        return extension;
+    }
+    if mime.ends_with("+json") {
+        "json"
+    } else if mime.ends_with("+xml") {
+        "xml"
+    } else if mime.starts_with("text/") {
+        "txt"
+    } else {
+        "bin"
+    }
+}
+
+// 抓包时间，本地时区
+fn date_time(ts: Duration) -> String {
+    har::local_time(ts)
+        .format("%Y-%m-%d %H:%M:%S%.3f")
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 每个测试使用单独的目录
+    fn export(name: &str) -> ObjectExport {
+        let dir =
+            std::env::temp_dir().join(format!("http_dump_objects_{}_{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        ObjectExport::create(dir.to_str().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn file_name_stays_in_dir() {
+        let export = export("traversal");
+        let names = [
+            ("http://example.com/../../etc/passwd", "passwd.txt"),
+            (
+                "http://example.com/a/..%2F..%2Fetc%2Fpasswd",
+                "_.._etc_passwd.txt",
+            ),
+            ("http://example.com/%2Fetc%2Fpasswd", "_etc_passwd.txt"),
+            ("http://example.com/..", "index.txt"),
+            ("http://example.com/", "index.txt"),
+            ("/etc/passwd", "passwd.txt"),
+            ("", "object.txt"),
+        ];
+        for (url, expect) in names {
+            assert_eq!(export.file_name(url, "text/plain", false), expect, "{url}");
+        }
+        fs::remove_dir_all(&export.dir).unwrap();
+    }
+
+    #[test]
+    fn file_name_dedup() {
+        let export = export("dedup");
+        let url = "http://example.com/a.json?x=1";
+        assert_eq!(export.file_name(url, "", false), "a.json");
+        fs::write(export.dir.join("a.json"), b"").unwrap();
+        assert_eq!(export.file_name(url, "", false), "a-1.json");
+        fs::write(export.dir.join("a-1.json"), b"").unwrap();
+        assert_eq!(export.file_name(url, "", false), "a-2.json");
+        fs::remove_dir_all(&export.dir).unwrap();
+    }
+
+    #[test]
+    fn file_name_extension() {
+        let export = export("extension");
+        let names = [
+            (
+                "http://example.com/logo.png",
+                "image/jpeg",
+                false,
+                "logo.png",
+            ),
+            (
+                "http://example.com/data",
+                "Text/HTML; charset=UTF-8",
+                false,
+                "data.html",
+            ),
+            (
+                "http://example.com/data",
+                "application/vnd.api+json",
+                false,
+                "data.json",
+            ),
+            (
+                "http://example.com/data.verylongext",
+                "",
+                false,
+                "data.verylongext.bin",
+            ),
+            // 请求体的扩展名只看Content-Type
+            (
+                "http://example.com/upload.png",
+                "application/json",
+                true,
+                "upload.png.request.json",
+            ),
+        ];
+        for (url, content_type, is_request, expect) in names {
+            assert_eq!(
+                export.file_name(url, content_type, is_request),
+                expect,
+                "{url}"
+            );
+        }
+        fs::remove_dir_all(&export.dir).unwrap();
+    }
+}
@@ -0,0 +1,321 @@
+use std::borrow::Cow;
+
+use super::binary;
+
+// 缩进
+const INDENT: &str = "  ";
+// html中没有结束标签的元素
+const VOID_ELEMENTS: [&str; 14] = [
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source",
+    "track", "wbr",
+];
+// html中内容原样保留的元素
+const RAW_TEXT_ELEMENTS: [&str; 3] = ["script", "style", "pre"];
+
+// 根据Content-Type格式化消息体，不支持的类型或者格式错误时原样返回
+pub fn pretty<'a>(content_type: Option<&str>, text: &'a str) -> Cow<'a, str> {
+    let pretty_text = match binary::mime(content_type).as_str() {
+        "application/json" | "text/json" => pretty_json(text),
+        x if x.ends_with("+json") => pretty_json(text),
+        "application/xml" | "text/xml" => pretty_markup(text, false),
+        x if x.ends_with("+xml") => pretty_markup(text, false),
+        "text/html" => pretty_markup(text, true),
+        "application/x-www-form-urlencoded" => Some(pretty_form(text)),
+        _ => None,
+    };
+    match pretty_text {
+        Some(pretty_text) => Cow::Owned(pretty_text),
+        None => Cow::Borrowed(text),
+    }
+}
+
+// json缩进，不改变内容和字段顺序，括号不匹配时返回None
+fn pretty_json(text: &str) -> Option<String> {
+    let mut out = String::with_capacity(text.len() * 2);
+    let mut stack = Vec::new();
+    let mut in_string = false;
+    let mut escape = false;
+    let mut chars = text.trim().chars().peekable();
+    while let Some(c) = chars.next() {
+        if in_string {
+            out.push(c);
+            if escape {
+                escape = false;
+            } else if c == '\\' {
+                escape = true;
+            } else if c == '"' {
+                in_string = false;
+            }
+            continue;
+        }
+        match c {
+            '"' => {
+                in_string = true;
+                out.push(c);
+            }
+            '{' | '[' => {
+                stack.push(if c == '{' { '}' } else { ']' });
+                out.push(c);
+                // 空对象、空数组不换行
+                while chars.next_if(|c| c.is_whitespace()).is_some() {}
+                if chars.peek() == stack.last() {
+                    out.push(chars.next()?);
+                    stack.pop();
+                } else {
+                    new_line(&mut out, stack.len());
+                }
+            }
+            '}' | ']' => {
+                if stack.pop() != Some(c) {
+                    return None;
+                }
+                new_line(&mut out, stack.len());
+                out.push(c);
+            }
+            ',' => {
+                out.push(c);
+                new_line(&mut out, stack.len());
+            }
+            ':' => out.push_str(": "),
+            c if c.is_whitespace() => {}
+            c => out.push(c),
+        }
+    }
+    if in_string || !stack.is_empty() {
+        return None;
+    }
+    Some(out)
+}
+
+// xml、html缩进，每个标签和文本单独一行
+fn pretty_markup(text: &str, html: bool) -> Option<String> {
+    let mut out = String::with_capacity(text.len() * 2);
+    let mut depth: usize = 0;
+    let mut rest = text.trim();
+    while !rest.is_empty() {
+        if !rest.starts_with('<') {
+            // 文本
+            let end = rest.find('<').unwrap_or(rest.len());
+            let content = rest[..end].trim();
+            if !content.is_empty() {
+                push_line(&mut out, depth, content);
+            }
+            rest = &rest[end..];
+            continue;
+        }
+        let end = tag_end(rest)?;
+        let tag = &rest[..end];
+        rest = &rest[end..];
+        if tag.starts_with("</") {
+            depth = depth.saturating_sub(1);
+            push_line(&mut out, depth, tag);
+            continue;
+        }
+        push_line(&mut out, depth, tag);
+        if tag.starts_with("<?") || tag.starts_with("<!") || tag.ends_with("/>") {
+            continue;
+        }
+        let name = tag[1..]
+            .split(|c: char| c.is_whitespace() || c == '>' || c == '/')
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        if html && VOID_ELEMENTS.contains(&name.as_str()) {
+            continue;
+        }
+        if html && RAW_TEXT_ELEMENTS.contains(&name.as_str()) {
+            // 内容原样输出，直到结束标签
+            let close = format!("</{name}");
+            let index = rest.to_ascii_lowercase().find(&close).unwrap_or(rest.len());
+            let content = rest[..index].trim_matches(['\r', '\n']);
+            if !content.trim().is_empty() {
+                out.push_str(content);
+                out.push('\n');
+            }
+            rest = &rest[index..];
+        } else if let Some((inline, next)) = inline_text(rest, &name) {
+            // 只有文本的元素，和标签放在同一行
+            out.pop();
+            out.push_str(inline);
+            out.push('\n');
+            rest = next;
+            continue;
+        }
+        depth += 1;
+    }
+    Some(out.trim_end().to_string())
+}
+
+// 元素内容只有文本时，返回文本加结束标签，以及剩余的数据
+fn inline_text<'a>(text: &'a str, name: &str) -> Option<(&'a str, &'a str)> {
+    let index = text.find('<')?;
+    if text[..index].contains('\n') {
+        return None;
+    }
+    let close = text[index..].strip_prefix("</")?;
+    let close_name = close.get(..name.len())?;
+    if !close_name.eq_ignore_ascii_case(name) || !close[name.len()..].trim_start().starts_with('>')
+    {
+        return None;
+    }
+    let end = index + 2 + name.len() + close[name.len()..].find('>')? + 1;
+    Some((&text[..end], &text[end..]))
+}
+
+// 标签结束位置，注释、CDATA到结束标记为止，属性值中可能有>
+fn tag_end(text: &str) -> Option<usize> {
+    for (start, end) in [("<!--", "-->"), ("<![CDATA[", "]]>")] {
+        if text.starts_with(start) {
+            return text.find(end).map(|index| index + end.len());
+        }
+    }
+    let mut quote = None;
+    for (index, c) in text.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if q == c => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '>') => return Some(index + 1),
+            _ => {}
+        }
+    }
+    None
+}
+
+// 表单，每个字段一行，解码后输出
+fn pretty_form(text: &str) -> String {
+    text.trim()
+        .split('&')
+        .filter(|field| !field.is_empty())
+        .map(|field| {
+            let (key, value) = field.split_once('=').unwrap_or((field, ""));
+            format!("{} = {}", url_decode(key), url_decode(value))
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+// 百分号解码，+是空格
+pub fn url_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        match bytes[index] {
+            b'+' => out.push(b' '),
+            b'%' => {
+                let hex = text
+                    .get(index + 1..index + 3)
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+                if let Some(byte) = hex {
+                    out.push(byte);
+                    index += 3;
+                    continue;
+                }
+                out.push(b'%');
+            }
+            byte => out.push(byte),
+        }
+        index += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+fn new_line(out: &mut String, depth: usize) {
+    out.push('\n');
+    out.push_str(&INDENT.repeat(depth));
+}
+
+fn push_line(out: &mut String, depth: usize, line: &str) {
+    out.push_str(&INDENT.repeat(depth));
+    out.push_str(line);
+    out.push('\n');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json() {
+        let text = r#"{"a":[1, 2],"b":{},"c":[ ],"d":"x{,}\"y","e":{"f":null}}"#;
+        assert_eq!(
+            pretty(Some("application/json; charset=utf-8"), text),
+            r#"{
+  "a": [
+    1,
+    2
+  ],
+  "b": {},
+  "c": [],
+  "d": "x{,}\"y",
+  "e": {
+    "f": null
+  }
+}"#
+        );
+        assert_eq!(pretty(Some("application/problem+json"), "[1]"), "[\n  1\n]");
+        // 括号不匹配、字符串没有结束时原样返回
+        for text in [r#"{"a":1"#, r#"{"a":1]"#, r#"{"a":"1}"#, "}"] {
+            assert_eq!(pretty(Some("application/json"), text), text);
+        }
+        assert_eq!(pretty(None, "{}"), "{}");
+    }
+
+    #[test]
+    fn xml() {
+        let text =
+            r#"<?xml version="1.0"?><a x="1>2"><b>text</b><c/><!-- <d> --><e><f>1</f></e></a>"#;
+        assert_eq!(
+            pretty(Some("Text/XML"), text),
+            r#"<?xml version="1.0"?>
+<a x="1>2">
+  <b>text</b>
+  <c/>
+  <!-- <d> -->
+  <e>
+    <f>1</f>
+  </e>
+</a>"#
+        );
+        // 标签没有结束时原样返回
+        assert_eq!(pretty(Some("application/xml"), "<a><b"), "<a><b");
+    }
+
+    #[test]
+    fn html() {
+        let text = "<html><head><meta charset=\"utf-8\"><script>if (a<b) {}</script></head>\
+                    <body><p>hi<br>there</p></body></html>";
+        assert_eq!(
+            pretty(Some("text/html"), text),
+            r#"<html>
+  <head>
+    <meta charset="utf-8">
+    <script>
+if (a<b) {}
+    </script>
+  </head>
+  <body>
+    <p>
+      hi
+      <br>
+      there
+    </p>
+  </body>
+</html>"#
+        );
+    }
+
+    #[test]
+    fn form() {
+        assert_eq!(
+            pretty(
+                Some("application/x-www-form-urlencoded"),
+                "name=%E4%BD%A0+%E5%A5%BD&empty=&flag&&a%3Db=100%25"
+            ),
+            "name = 你 好\nempty = \nflag = \na=b = 100%"
+        );
+        assert_eq!(url_decode("%zz%4"), "%zz%4");
+        assert_eq!(url_decode("a+b%2Bc"), "a b+c");
+    }
+}
@@ -0,0 +1,389 @@
+use std::{borrow::Cow, ops::Range};
+
+use super::{binary, message::find_head_end, pretty};
+
+// 脱敏后显示的值
+const MASK: &[u8] = b"******";
+// 默认脱敏的http头
+const DEFAULT_HEADERS: [&str; 9] = [
+    "Authorization",
+    "Proxy-Authorization",
+    "Cookie",
+    "Set-Cookie",
+    "X-Api-Key",
+    "Api-Key",
+    "X-Auth-Token",
+    "X-Access-Token",
+    "X-Csrf-Token",
+];
+
+// 敏感信息脱敏，名称都忽略大小写
+// 文本输出时替换成******，写pcap文件时长度不变，用*覆盖
+#[derive(Debug, Clone)]
+pub struct Redact {
+    // http头，包含默认的敏感头
+    pub headers: Vec<String>,
+    // 查询参数，也用于表单消息体
+    pub queries: Vec<String>,
+    // json字段路径，没有.时匹配任意层级的同名字段，有.时从根开始匹配，*匹配一层
+    pub json_fields: Vec<String>,
+    // 写pcap文件时，报文也脱敏
+    pub pcap: bool,
+}
+
+impl Redact {
+    pub fn new() -> Self {
+        Redact {
+            headers: DEFAULT_HEADERS
+                .iter()
+                .map(|name| name.to_string())
+                .collect(),
+            queries: Vec::new(),
+            json_fields: Vec::new(),
+            pcap: false,
+        }
+    }
+
+    // 首行和http头脱敏
+    // start_line: 第一行是否是首行，尾部字段没有首行
+    pub fn head<'a>(&self, head: &'a [u8], start_line: bool) -> Cow<'a, [u8]> {
+        replace(head, self.head_ranges(head, start_line, 0))
+    }
+
+    // 一行文本中的查询参数脱敏，比如请求行
+    pub fn line(&self, line: &str) -> String {
+        let ranges = self.target_ranges(line.as_bytes(), 0);
+        String::from_utf8_lossy(&replace(line.as_bytes(), ranges)).into_owned()
+    }
+
+    // 消息体脱敏，json字段、表单字段
+    pub fn body<'a>(&self, content_type: Option<&str>, text: &'a str) -> Cow<'a, str> {
+        let ranges = match mime(content_type) {
+            Mime::Json => self.json_ranges(text.as_bytes(), 0),
+            Mime::Form => self.query_ranges(text.as_bytes(), 0),
+            Mime::Other => return Cow::Borrowed(text),
+        };
+        match replace(text.as_bytes(), ranges) {
+            Cow::Borrowed(_) => Cow::Borrowed(text),
+            Cow::Owned(data) => Cow::Owned(String::from_utf8_lossy(&data).into_owned()),
+        }
+    }
+
+    // 报文负载脱敏，长度不变，用*覆盖
+    // 只处理报文中能看到的明文，压缩的消息体、跨报文的字段不处理
+    pub fn payload(&self, data: &mut [u8]) {
+        let (head_len, body_start) = match find_head_end(data) {
+            Some(head_end) => head_end,
+            // 没有http头，只有消息体
+            None if data.trim_ascii_start().starts_with(b"{")
+                || data.trim_ascii_start().starts_with(b"[") =>
+            {
+                (0, 0)
+            }
+            None => (data.len(), data.len()),
+        };
+        let head = &data[..head_len];
+        let start_line = head
+            .split(|b| *b == b'\n')
+            .next()
+            .is_some_and(|line| contains(line, b" HTTP/"));
+        let mut ranges = self.head_ranges(head, start_line, 0);
+        let body = &data[body_start..];
+        if body.trim_ascii_start().starts_with(b"{") || body.trim_ascii_start().starts_with(b"[") {
+            ranges.extend(self.json_ranges(body, body_start));
+        } else if contains(
+            &head.to_ascii_lowercase(),
+            b"application/x-www-form-urlencoded",
+        ) {
+            ranges.extend(self.query_ranges(body, body_start));
+        }
+        for range in ranges {
+            let value = &mut data[range];
+            // 字符串保留引号
+            let value = match value {
+                [b'"', inner @ .., b'"'] => inner,
+                value => value,
+            };
+            value.fill(b'*');
+        }
+    }
+
+    // 需要脱敏的http头的值、请求行中的查询参数
+    fn head_ranges(&self, head: &[u8], start_line: bool, offset: usize) -> Vec<Range<usize>> {
+        let mut ranges = Vec::new();
+        let mut start = 0;
+        for (index, line) in head.split(|b| *b == b'\n').enumerate() {
+            let line_start = start;
+            start += line.len() + 1;
+            let line = line.strip_suffix(b"\r").unwrap_or(line);
+            if index == 0 && start_line {
+                ranges.extend(self.target_ranges(line, offset + line_start));
+                continue;
+            }
+            let Some(colon) = line.iter().position(|b| *b == b':') else {
+                continue;
+            };
+            let name = &line[..colon];
+            if name.is_empty()
+                || !name
+                    .iter()
+                    .all(|b| b.is_ascii_alphanumeric() || *b == b'-' || *b == b'_')
+                || !self
+                    .headers
+                    .iter()
+                    .any(|header| header.as_bytes().eq_ignore_ascii_case(name))
+            {
+                continue;
+            }
+            let value = &line[colon + 1..];
+            let value_start = colon + 1 + (value.len() - value.trim_ascii_start().len());
+            let value_end = colon + 1 + value.trim_ascii_end().len();
+            if value_start < value_end {
+                ranges.push(offset + line_start + value_start..offset + line_start + value_end);
+            }
+        }
+        ranges
+    }
+
+    // 请求目标中的查询参数，GET /path?token=xxx HTTP/1.1
+    fn target_ranges(&self, line: &[u8], offset: usize) -> Vec<Range<usize>> {
+        let Some(start) = line.iter().position(|b| *b == b'?') else {
+            return Vec::new();
+        };
+        let end = line[start..]
+            .iter()
+            .position(|b| *b == b' ' || *b == b'#')
+            .map_or(line.len(), |index| start + index);
+        self.query_ranges(&line[start + 1..end], offset + start + 1)
+    }
+
+    // 查询参数、表单字段，a=1&b=2，参数名解码后比较
+    fn query_ranges(&self, query: &[u8], offset: usize) -> Vec<Range<usize>> {
+        let mut ranges = Vec::new();
+        if self.queries.is_empty() {
+            return ranges;
+        }
+        let mut start = 0;
+        for field in query.split(|b| *b == b'&') {
+            let field_start = start;
+            start += field.len() + 1;
+            let Some(eq) = field.iter().position(|b| *b == b'=') else {
+                continue;
+            };
+            let name = pretty::url_decode(String::from_utf8_lossy(&field[..eq]).trim());
+            if eq + 1 < field.len()
+                && self
+                    .queries
+                    .iter()
+                    .any(|query| query.eq_ignore_ascii_case(&name))
+            {
+                ranges.push(offset + field_start + eq + 1..offset + field_start + field.len());
+            }
+        }
+        ranges
+    }
+
+    // json中需要脱敏的值，不解析整个文档，格式错误时也尽量处理
+    fn json_ranges(&self, data: &[u8], offset: usize) -> Vec<Range<usize>> {
+        let mut ranges = Vec::new();
+        if self.json_fields.is_empty() {
+            return ranges;
+        }
+        // 每一层的当前字段名，数组为None
+        let mut stack: Vec<Option<String>> = Vec::new();
+        // 下一个字符串是字段名
+        let mut key_next = false;
+        let mut index = 0;
+        while index < data.len() {
+            let byte = data[index];
+            // 对象中字段的值，字段路径匹配时，整个值脱敏
+            let value_start = !key_next
+                && matches!(stack.last(), Some(Some(_)))
+                && !matches!(byte, b',' | b':' | b'}' | b']')
+                && !byte.is_ascii_whitespace();
+            if value_start && self.json_match(&stack) {
+                let end = value_end(data, index);
+                ranges.push(offset + index..offset + end);
+                index = end;
+                continue;
+            }
+            match byte {
+                b'"' => {
+                    let end = string_end(data, index);
+                    if key_next {
+                        if let Some(Some(key)) = stack.last_mut() {
+                            let inner = &data[index + 1..end.saturating_sub(1).max(index + 1)];
+                            *key = String::from_utf8_lossy(inner).into_owned();
+                        }
+                        key_next = false;
+                    }
+                    index = end;
+                    continue;
+                }
+                b'{' => {
+                    stack.push(Some(String::new()));
+                    key_next = true;
+                }
+                b'[' => stack.push(None),
+                b'}' | b']' => {
+                    stack.pop();
+                    key_next = false;
+                }
+                b',' => key_next = matches!(stack.last(), Some(Some(_))),
+                _ => {}
+            }
+            index += 1;
+        }
+        ranges
+    }
+
+    // 字段路径是否需要脱敏，数组不算一层
+    fn json_match(&self, stack: &[Option<String>]) -> bool {
+        let path: Vec<&str> = stack.iter().flatten().map(String::as_str).collect();
+        self.json_fields.iter().any(|field| {
+            let segments: Vec<&str> = field.split('.').collect();
+            if segments.len() == 1 {
+                return path
+                    .last()
+                    .is_some_and(|key| key.eq_ignore_ascii_case(field));
+            }
+            segments.len() == path.len()
+                && segments
+                    .iter()
+                    .zip(&path)
+                    .all(|(segment, key)| *segment == "*" || segment.eq_ignore_ascii_case(key))
+        })
+    }
+}
+
+// 消息体类型
+enum Mime {
+    Json,
+    Form,
+    Other,
+}
+
+fn mime(content_type: Option<&str>) -> Mime {
+    match binary::mime(content_type).as_str() {
+        "application/json" | "text/json" => Mime::Json,
+        x if x.ends_with("+json") => Mime::Json,
+        "application/x-www-form-urlencoded" => Mime::Form,
+        _ => Mime::Other,
+    }
+}
+
+// 替换成******，ranges按位置排序且不重叠
+fn replace(data: &[u8], ranges: Vec<Range<usize>>) -> Cow<'_, [u8]> {
+    if ranges.is_empty() {
+        return Cow::Borrowed(data);
+    }
+    let mut out = Vec::with_capacity(data.len());
+    let mut last = 0;
+    for range in ranges {
+        out.extend_from_slice(&data[last..range.start]);
+        // 字符串保留引号
+        if data[range.start] == b'"' {
+            out.push(b'"');
+            out.extend_from_slice(MASK);
+            out.push(b'"');
+        } else {
+            out.extend_from_slice(MASK);
+        }
+        last = range.end;
+    }
+    out.extend_from_slice(&data[last..]);
+    Cow::Owned(out)
+}
+
+// 字符串结束位置，包含结束的引号，没有结束时到数据末尾
+fn string_end(data: &[u8], start: usize) -> usize {
+    let mut index = start + 1;
+    while index < data.len() {
+        match data[index] {
+            b'\\' => index += 2,
+            b'"' => return index + 1,
+            _ => index += 1,
+        }
+    }
+    data.len()
+}
+
+// json值结束位置
+fn value_end(data: &[u8], start: usize) -> usize {
+    match data[start] {
+        b'"' => string_end(data, start),
+        b'{' | b'[' => {
+            let mut depth = 0;
+            let mut index = start;
+            while index < data.len() {
+                match data[index] {
+                    b'"' => {
+                        index = string_end(data, index);
+                        continue;
+                    }
+                    b'{' | b'[' => depth += 1,
+                    b'}' | b']' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            return index + 1;
+                        }
+                    }
+                    _ => {}
+                }
+                index += 1;
+            }
+            data.len()
+        }
+        // 数字、true、false、null
+        _ => data[start..]
+            .iter()
+            .position(|b| matches!(b, b',' | b'}' | b']') || b.is_ascii_whitespace())
+            .map_or(data.len(), |index| start + index),
+    }
+}
+
+fn contains(data: &[u8], pattern: &[u8]) -> bool {
+    data.windows(pattern.len()).any(|window| window == pattern)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn redact() -> Redact {
+        let mut redact = Redact::new();
+        redact.queries.push("token".to_string());
+        redact.json_fields.push("password".to_string());
+        redact
+    }
+
+    #[test]
+    fn payload_keeps_length() {
+        let data =
+            b"POST /login?token=abc HTTP/1.1\r\nCookie: s=1\r\n\r\n{\"password\": \"secret\"}";
+        let mut masked = data.to_vec();
+        redact().payload(&mut masked);
+        assert_eq!(
+            masked,
+            b"POST /login?token=*** HTTP/1.1\r\nCookie: ***\r\n\r\n{\"password\": \"******\"}"
+        );
+        // 只用\n换行
+        let mut masked = b"GET /?token=1 HTTP/1.1\nAuthorization: x\n\n".to_vec();
+        redact().payload(&mut masked);
+        assert_eq!(masked, b"GET /?token=* HTTP/1.1\nAuthorization: *\n\n");
+    }
+
+    #[test]
+    fn body_by_content_type() {
+        let redact = redact();
+        assert_eq!(
+            redact.body(Some("application/json; charset=utf-8"), r#"{"password":1}"#),
+            r#"{"password":******}"#
+        );
+        assert_eq!(
+            redact.body(Some("Application/X-WWW-Form-Urlencoded"), "a=1&token=2"),
+            "a=1&token=******"
+        );
+        assert_eq!(redact.body(Some("text/plain"), "token=2"), "token=2");
+    }
+}
@@ -0,0 +1,288 @@
+use std::{
+    collections::VecDeque,
+    io::{Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    thread,
+    time::{Duration, Instant},
+};
+
+use super::{
+    har::millis,
+    message::{HttpMessage, MessageParser},
+};
+
+// 连接、读写超时
+const REPLAY_TIMEOUT: Duration = Duration::from_secs(10);
+
+// 回放请求到指定的服务端，每个请求使用新的连接
+#[derive(Debug)]
+pub struct Replay {
+    // 服务端，主机:端口
+    pub target: String,
+    // 改写的http头，值为None时删除
+    pub headers: Vec<(String, Option<String>)>,
+    // 回放速度倍数，1按照抓包时的间隔，0不等待
+    pub speed: f64,
+    // 第一个请求的抓包时间，以及开始回放的时间
+    start: Option<(Duration, Instant)>,
+}
+
+// 回放结果，状态码、耗时
+pub type ReplayResult = Result<(u16, Duration), String>;
+
+impl Replay {
+    pub fn new(target: String) -> Self {
+        Replay {
+            target,
+            headers: Vec::new(),
+            speed: 1.0,
+            start: None,
+        }
+    }
+
+    // 改写的http头，Name: value 替换或者增加，Name: 删除
+    pub fn parse_header(text: &str) -> Option<(String, Option<String>)> {
+        let (name, value) = text.split_once(':')?;
+        let name = name.trim();
+        if name.is_empty() {
+            return None;
+        }
+        let value = value.trim();
+        let value = if value.is_empty() {
+            None
+        } else {
+            Some(value.to_string())
+        };
+        Some((name.to_string(), value))
+    }
+
+    // 按照抓包时的间隔等待，ts是请求的抓包时间
+    pub fn wait(&mut self, ts: Duration) {
+        let (first_ts, start) = *self.start.get_or_insert((ts, Instant::now()));
+        if self.speed <= 0.0 {
+            return;
+        }
+        let offset = ts.saturating_sub(first_ts).div_f64(self.speed);
+        let elapsed = start.elapsed();
+        if offset > elapsed {
+            thread::sleep(offset - elapsed);
+        }
+    }
+
+    // 发送请求，返回最终响应的状态码，以及从发送请求到收完响应的耗时
+    pub fn send(&self, request: &HttpMessage) -> ReplayResult {
+        let addr = self
+            .target
+            .to_socket_addrs()
+            .ok()
+            .and_then(|mut addrs| addrs.next())
+            .ok_or_else(|| format!("服务端地址错误: {}", self.target))?;
+        let mut stream = TcpStream::connect_timeout(&addr, REPLAY_TIMEOUT)
+            .map_err(|error| format!("连接失败: {error}"))?;
+        let _ = stream.set_read_timeout(Some(REPLAY_TIMEOUT));
+        let _ = stream.set_write_timeout(Some(REPLAY_TIMEOUT));
+        let start = Instant::now();
+        stream
+            .write_all(&self.request_data(request))
+            .map_err(|error| format!("发送请求失败: {error}"))?;
+
+        let mut parser = MessageParser::new();
+        let mut methods = VecDeque::from_iter(request.method().map(String::from));
+        let mut buf = [0; 16 * 1024];
+        loop {
+            let len = stream
+                .read(&mut buf)
+                .map_err(|error| format!("读取响应失败: {error}"))?;
+            let messages = if len == 0 {
+                parser.close().into_iter().collect()
+            } else {
+                parser.push(&buf[..len], Duration::ZERO, &mut methods)
+            };
+            // 1xx是临时响应
+            let status = messages
+                .iter()
+                .filter_map(HttpMessage::status)
+                .find(|status| !(100..200).contains(status) || *status == 101);
+            if let Some(status) = status {
+                return Ok((status, start.elapsed()));
+            }
+            if len == 0 {
+                return Err("连接已关闭，没有收到响应".to_string());
+            }
+        }
+    }
+
+    // 改写http头后的请求，消息体原样发送
+    // 每个请求单独一个连接，发送完由服务端关闭
+    fn request_data(&self, request: &HttpMessage) -> Vec<u8> {
+        let mut headers: Vec<(String, Option<String>)> = request
+            .headers
+            .iter()
+            .map(|(name, value)| (name.to_string(), Some(value.to_string())))
+            .collect();
+        let connection = ("Connection".to_string(), Some("close".to_string()));
+        for (name, value) in self.headers.iter().chain([&connection]) {
+            let mut found = false;
+            for (header_name, header_value) in headers.iter_mut() {
+                if header_name.eq_ignore_ascii_case(name) {
+                    // 同名的头只保留第一个
+                    *header_value = if found { None } else { value.clone() };
+                    found = true;
+                }
+            }
+            if !found {
+                headers.push((name.clone(), value.clone()));
+            }
+        }
+
+        let mut data = request.start_line.to_string().into_bytes();
+        data.extend_from_slice(b"\r\n");
+        for (name, value) in headers {
+            if let Some(value) = value {
+                data.extend_from_slice(format!("{name}: {value}\r\n").as_bytes());
+            }
+        }
+        data.extend_from_slice(b"\r\n");
+        data.extend_from_slice(&request.body);
+        data
+    }
+}
+
+// 回放报告，和抓包时的响应比较
+// captured: 抓包时的状态码和耗时，没有响应时为None
+pub fn report(
+    request_line: &str,
+    captured: Option<(u16, Duration)>,
+    replayed: &ReplayResult,
+) -> String {
+    let mut report = format!("# 回放 {request_line}  抓包: ");
+    match captured {
+        Some((status, duration)) => report.push_str(&format!("{status} {:.3}ms", millis(duration))),
+        None => report.push_str("(没有响应)"),
+    }
+    report.push_str("  回放: ");
+    match replayed {
+        Ok((status, duration)) => {
+            report.push_str(&format!("{status} {:.3}ms", millis(*duration)));
+            if let Some((captured_status, captured_duration)) = captured {
+                report.push_str(&format!(
+                    "  耗时差: {:+.3}ms",
+                    millis(*duration) - millis(captured_duration)
+                ));
+                if captured_status != *status {
+                    report.push_str("  状态码不同");
+                }
+            }
+        }
+        Err(error) => report.push_str(&format!("失败 {error}")),
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+
+    use super::*;
+
+    // 本地服务端，收到一个请求后返回response，返回收到的请求
+    fn server(response: &'static [u8]) -> (String, thread::JoinHandle<Vec<u8>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let target = listener.local_addr().unwrap().to_string();
+        let handle = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0; 1024];
+            // 测试的请求体是4字节
+            while !request.ends_with(b"\r\n\r\nbody") {
+                let len = stream.read(&mut buf).unwrap();
+                if len == 0 {
+                    break;
+                }
+                request.extend_from_slice(&buf[..len]);
+            }
+            thread::sleep(Duration::from_millis(20));
+            stream.write_all(response).unwrap();
+            request
+        });
+        (target, handle)
+    }
+
+    fn request() -> HttpMessage {
+        HttpMessage::parse(
+            b"POST /orders?id=1 HTTP/1.1\r\nHost: example.com\r\nAuthorization: old\r\n\
+            X-Drop: 1\r\nConnection: keep-alive\r\nConnection: upgrade\r\nContent-Length: 4\r\n\r\nbody",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn send_rewrites_headers() {
+        let (target, handle) = server(
+            b"HTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 201 Created\r\nContent-Length: 2\r\n\r\nok",
+        );
+        let mut replay = Replay::new(target);
+        for header in ["Authorization: Bearer new", "X-Drop:", "X-Added: yes"] {
+            replay.headers.push(Replay::parse_header(header).unwrap());
+        }
+        let (status, duration) = replay.send(&request()).unwrap();
+        assert_eq!(status, 201);
+        assert!(duration >= Duration::from_millis(20));
+        assert!(duration < REPLAY_TIMEOUT);
+        let received = handle.join().unwrap();
+        assert_eq!(
+            String::from_utf8(received).unwrap(),
+            "POST /orders?id=1 HTTP/1.1\r\nHost: example.com\r\nAuthorization: Bearer new\r\n\
+            Connection: close\r\nContent-Length: 4\r\nX-Added: yes\r\n\r\nbody"
+        );
+    }
+
+    #[test]
+    fn request_data_adds_connection_close() {
+        let request = HttpMessage::parse(b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n").unwrap();
+        let data = Replay::new(String::new()).request_data(&request);
+        assert_eq!(
+            data,
+            b"GET / HTTP/1.1\r\nHost: example.com\r\nConnection: close\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn send_errors() {
+        // 没有收到响应
+        let (target, handle) = server(b"");
+        let replayed = Replay::new(target).send(&request());
+        handle.join().unwrap();
+        assert_eq!(replayed, Err("连接已关闭，没有收到响应".to_string()));
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let target = listener.local_addr().unwrap().to_string();
+        drop(listener);
+        let replayed = Replay::new(target).send(&request());
+        assert!(replayed.unwrap_err().starts_with("连接失败"));
+    }
+
+    #[test]
+    fn report_compares_with_capture() {
+        let captured = Some((200, Duration::from_millis(10)));
+        let replayed = Ok((500, Duration::from_millis(15)));
+        assert_eq!(
+            report("GET / HTTP/1.1", captured, &replayed),
+            "# 回放 GET / HTTP/1.1  抓包: 200 10.000ms  回放: 500 15.000ms  耗时差: +5.000ms  状态码不同"
+        );
+    }
+
+    #[test]
+    fn parse_header() {
+        assert_eq!(
+            Replay::parse_header(" X-Token : a:b "),
+            Some(("X-Token".to_string(), Some("a:b".to_string())))
+        );
+        assert_eq!(
+            Replay::parse_header("X-Token:"),
+            Some(("X-Token".to_string(), None))
+        );
+        assert_eq!(Replay::parse_header(": value"), None);
+        assert_eq!(Replay::parse_header("no colon"), None);
+    }
+}
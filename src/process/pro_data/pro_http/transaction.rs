@@ -0,0 +1,57 @@
+use std::time::Duration;
+
+use crate::stream::StreamKey;
+
+use super::{message::HttpMessage, replay::ReplayResult};
+
+// 一次http请求和对应的响应
+// 抓包开始时连接已经建立，或者连接结束时还没有响应，请求、响应可能只有一个
+#[derive(Debug)]
+pub struct HttpTransaction {
+    // 请求方向，客户端 -> 服务端
+    pub key: StreamKey,
+    pub request: Option<HttpMessage>,
+    pub response: Option<HttpMessage>,
+    // 回放结果，回放时在请求完成时发送
+    pub replayed: Option<ReplayResult>,
+}
+
+impl HttpTransaction {
+    // 首字节时间，请求发送完到响应第一个字节到达
+    pub fn time_to_first_byte(&self) -> Option<Duration> {
+        let (request, response) = (self.request.as_ref()?, self.response.as_ref()?);
+        Some(response.first_ts.saturating_sub(request.last_ts))
+    }
+
+    // 总耗时，请求第一个字节到响应最后一个字节
+    pub fn duration(&self) -> Option<Duration> {
+        let (request, response) = (self.request.as_ref()?, self.response.as_ref()?);
+        Some(response.last_ts.saturating_sub(request.first_ts))
+    }
+
+    // 概要，首行和耗时
+    pub fn summary(&self) -> String {
+        let request_line = self
+            .request
+            .as_ref()
+            .map(|message| message.start_line.to_string())
+            .unwrap_or_else(|| "(没有请求)".to_string());
+        let status_line = self
+            .response
+            .as_ref()
+            .map(|message| message.start_line.to_string())
+            .unwrap_or_else(|| "(没有响应)".to_string());
+        let mut summary = format!(
+            "# {} -> {}  {request_line}  =>  {status_line}",
+            self.key.src, self.key.dst
+        );
+        if let (Some(ttfb), Some(duration)) = (self.time_to_first_byte(), self.duration()) {
+            summary.push_str(&format!(
+                "  首字节: {:.3}ms  总耗时: {:.3}ms",
+                ttfb.as_secs_f64() * 1000.0,
+                duration.as_secs_f64() * 1000.0
+            ));
+        }
+        summary
+    }
+}
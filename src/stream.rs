@@ -0,0 +1,585 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    mem,
+    net::SocketAddr,
+    time::Duration,
+};
+
+use crate::{
+    analyze::{self, ApplicationPro, ProType},
+    PacketInfo,
+};
+
+// 连接空闲超时时间，超时后认为连接结束
+// 按抓包时间计算，从文件读取时也一样
+const IDLE_TIMEOUT: Duration = Duration::from_secs(120);
+// 检查超时连接的间隔
+const SWEEP_INTERVAL: Duration = Duration::from_secs(5);
+// 每个方向上，乱序等待的数据最多缓存的字节数，超过后跳过缺失的数据
+const MAX_PENDING_BYTES: usize = 4 * 1024 * 1024;
+// 最多同时跟踪的连接数，超过后关闭最久没有数据的连接
+const MAX_CONNECTIONS: usize = 10_000;
+
+// 连接的一个方向，源地址 -> 目的地址
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct StreamKey {
+    pub src: SocketAddr,
+    pub dst: SocketAddr,
+}
+
+impl StreamKey {
+    // 反方向
+    pub fn reverse(&self) -> StreamKey {
+        StreamKey {
+            src: self.dst,
+            dst: self.src,
+        }
+    }
+
+    // 连接标识，两个方向相同
+    fn conn_key(&self) -> (SocketAddr, SocketAddr) {
+        if self.src <= self.dst {
+            (self.src, self.dst)
+        } else {
+            (self.dst, self.src)
+        }
+    }
+}
+
+// 连接结束原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CloseReason {
+    // 两个方向都收到FIN
+    Fin,
+    // 收到RST
+    Rst,
+    // 空闲超时
+    Timeout,
+    // 连接数超过限制
+    Evicted,
+    // 捕获结束
+    End,
+}
+
+// 流重组产生的事件
+#[derive(Debug)]
+pub enum StreamEvent {
+    // 某个方向上，按序到达的数据
+    // application_pro是用连接上第一段数据识别的应用层协议
+    // ts是数据可以按序交付时的抓包时间
+    Data {
+        key: StreamKey,
+        application_pro: ApplicationPro,
+        ts: Duration,
+        data: Vec<u8>,
+    },
+    // 连接结束，key是连接上第一个发送数据的方向
+    Close {
+        key: StreamKey,
+        reason: CloseReason,
+    },
+}
+
+// 连接的一个方向
+#[derive(Debug, Default)]
+struct HalfStream {
+    // 下一个期望的序列号，收到SYN或者第一段数据时确定
+    next_seq: Option<u32>,
+    // 乱序到达的数据，key是相对于next_seq的偏移量展开后的绝对位置
+    pending: BTreeMap<u64, Vec<u8>>,
+    pending_bytes: usize,
+    // 已经按序交付的字节数，也是next_seq对应的绝对位置
+    offset: u64,
+    // FIN对应的绝对位置
+    fin_offset: Option<u64>,
+}
+
+impl HalfStream {
+    // 序列号转为绝对位置，处理序列号回绕
+    fn absolute(&self, next_seq: u32, seq: u32) -> i64 {
+        self.offset as i64 + seq.wrapping_sub(next_seq) as i32 as i64
+    }
+
+    // 收到报文段，返回可以按序交付的数据
+    fn push(&mut self, pro_type: &ProType, payload: &[u8], out: &mut Vec<u8>) {
+        let seq = pro_type.tcp_seq;
+        if pro_type.tcp_flags & analyze::TCP_SYN != 0 {
+            // SYN占一个序列号，重传的SYN也是一样的
+            if self.next_seq.is_none() {
+                self.next_seq = Some(seq.wrapping_add(1));
+            }
+            return;
+        }
+        let next_seq = *self.next_seq.get_or_insert(seq);
+        let start = self.absolute(next_seq, seq);
+        if pro_type.tcp_flags & analyze::TCP_FIN != 0 && self.fin_offset.is_none() {
+            self.fin_offset = Some((start + payload.len() as i64).max(0) as u64);
+        }
+        let end = start + payload.len() as i64;
+        if payload.is_empty() || end <= self.offset as i64 {
+            // 没有数据，或者是重传
+            return;
+        }
+        // 和已经交付的数据重叠，去掉重叠部分
+        let skip = (self.offset as i64 - start).max(0) as usize;
+        // 开始位置可能在第一个序列号之前，先按有符号数计算
+        let start = (start + skip as i64) as u64;
+        let payload = &payload[skip..];
+        if start > self.offset {
+            // 乱序，先缓存，同一位置保留较长的
+            let exist_len = self.pending.get(&start).map(|d| d.len()).unwrap_or(0);
+            if payload.len() > exist_len {
+                self.pending_bytes = self.pending_bytes + payload.len() - exist_len;
+                self.pending.insert(start, payload.to_vec());
+            }
+            if self.pending_bytes > MAX_PENDING_BYTES {
+                // 缺失的数据一直没到，跳过
+                self.skip_gap(next_seq);
+                self.drain(out);
+            }
+            return;
+        }
+        out.extend_from_slice(payload);
+        self.advance(next_seq, payload.len());
+        self.drain(out);
+    }
+
+    // 交付缓存中已经连续的数据
+    fn drain(&mut self, out: &mut Vec<u8>) {
+        while let Some(entry) = self.pending.first_entry() {
+            let start = *entry.key();
+            if start > self.offset {
+                break;
+            }
+            let data = entry.remove();
+            self.pending_bytes -= data.len();
+            let skip = (self.offset - start) as usize;
+            if skip < data.len() {
+                out.extend_from_slice(&data[skip..]);
+                let next_seq = self.next_seq.unwrap_or_default();
+                self.advance(next_seq, data.len() - skip);
+            }
+        }
+    }
+
+    // 跳到第一段缓存的数据，丢弃中间缺失的部分
+    fn skip_gap(&mut self, next_seq: u32) {
+        if let Some(start) = self.pending.keys().next().copied() {
+            if start > self.offset {
+                self.advance(next_seq, (start - self.offset) as usize);
+            }
+        }
+    }
+
+    // 交付数据后，移动期望的序列号
+    fn advance(&mut self, next_seq: u32, len: usize) {
+        self.next_seq = Some(next_seq.wrapping_add(len as u32));
+        self.offset += len as u64;
+    }
+
+    // 还没有收到过数据
+    fn is_empty(&self) -> bool {
+        self.offset == 0 && self.pending.is_empty()
+    }
+
+    // 这个方向的数据都交付了，并且收到了FIN
+    fn finished(&self) -> bool {
+        self.fin_offset.is_some_and(|fin| fin <= self.offset)
+    }
+}
+
+// TCP连接
+#[derive(Debug)]
+struct Connection {
+    // 第一个发送数据的方向，一般是客户端 -> 服务端
+    key: StreamKey,
+    // 用第一段数据识别的应用层协议
+    application_pro: Option<ApplicationPro>,
+    // 两个方向，和key同向的是client
+    client: HalfStream,
+    server: HalfStream,
+    last_seen: Duration,
+}
+
+impl Connection {
+    fn new(key: StreamKey, now: Duration) -> Self {
+        Connection {
+            key,
+            application_pro: None,
+            client: HalfStream::default(),
+            server: HalfStream::default(),
+            last_seen: now,
+        }
+    }
+}
+
+// TCP流重组
+// 按序列号排序，处理重传和重叠，FIN、RST、超时后结束连接
+#[derive(Debug, Default)]
+pub struct Reassembler {
+    connections: HashMap<(SocketAddr, SocketAddr), Connection>,
+    last_sweep: Option<Duration>,
+}
+
+impl Reassembler {
+    pub fn new() -> Self {
+        Reassembler::default()
+    }
+
+    // 处理一个报文，pro_type是TCP所在的一层（有隧道时是内层）
+    pub fn push(&mut self, packet_info: &PacketInfo, pro_type: &ProType) -> Vec<StreamEvent> {
+        let mut events = Vec::new();
+        let now = packet_info.ts;
+        self.sweep(now, &mut events);
+        let (Some(src), Some(dst)) = (pro_type.src_addr(), pro_type.dst_addr()) else {
+            return events;
+        };
+        let key = StreamKey { src, dst };
+        let conn_key = key.conn_key();
+        if !self.connections.contains_key(&conn_key) {
+            if pro_type.tcp_flags & analyze::TCP_RST != 0 {
+                return events;
+            }
+            if self.connections.len() >= MAX_CONNECTIONS {
+                self.evict(&mut events);
+            }
+            self.connections.insert(conn_key, Connection::new(key, now));
+        }
+        let Some(connection) = self.connections.get_mut(&conn_key) else {
+            return events;
+        };
+        connection.last_seen = now;
+        // 连接建立时，SYN的方向是客户端
+        // 已经收到数据后，重传或者反向的SYN不改变方向，避免两个方向的数据错位
+        if pro_type.tcp_flags & (analyze::TCP_SYN | analyze::TCP_ACK) == analyze::TCP_SYN
+            && key != connection.key
+            && connection.client.is_empty()
+            && connection.server.is_empty()
+        {
+            connection.key = key;
+            mem::swap(&mut connection.client, &mut connection.server);
+        }
+        let payload = packet_info
+            .data
+            .get(pro_type.application_start..pro_type.packet_end)
+            .unwrap_or_default();
+        let half = if key == connection.key {
+            &mut connection.client
+        } else {
+            &mut connection.server
+        };
+        let mut data = Vec::new();
+        half.push(pro_type, payload, &mut data);
+        if !data.is_empty() {
+            let application_pro = *connection
+                .application_pro
+                .get_or_insert_with(|| analyze::analyze_application_payload(&data));
+            events.push(StreamEvent::Data {
+                key,
+                application_pro,
+                ts: now,
+                data,
+            });
+        }
+        if pro_type.tcp_flags & analyze::TCP_RST != 0 {
+            self.close(conn_key, CloseReason::Rst, &mut events);
+        } else if connection.client.finished() && connection.server.finished() {
+            self.close(conn_key, CloseReason::Fin, &mut events);
+        }
+        events
+    }
+
+    // 捕获结束，关闭所有连接
+    pub fn finish(&mut self) -> Vec<StreamEvent> {
+        let mut events = Vec::new();
+        let conn_keys: Vec<_> = self.connections.keys().copied().collect();
+        for conn_key in conn_keys {
+            self.close(conn_key, CloseReason::End, &mut events);
+        }
+        events
+    }
+
+    // 关闭超时的连接
+    fn sweep(&mut self, now: Duration, events: &mut Vec<StreamEvent>) {
+        let last_sweep = *self.last_sweep.get_or_insert(now);
+        if now.saturating_sub(last_sweep) < SWEEP_INTERVAL {
+            return;
+        }
+        self.last_sweep = Some(now);
+        let conn_keys: Vec<_> = self
+            .connections
+            .iter()
+            .filter(|(_, connection)| now.saturating_sub(connection.last_seen) >= IDLE_TIMEOUT)
+            .map(|(conn_key, _)| *conn_key)
+            .collect();
+        for conn_key in conn_keys {
+            self.close(conn_key, CloseReason::Timeout, events);
+        }
+    }
+
+    // 连接数超过限制，关闭最久没有数据的连接
+    fn evict(&mut self, events: &mut Vec<StreamEvent>) {
+        let oldest = self
+            .connections
+            .iter()
+            .min_by_key(|(_, connection)| connection.last_seen)
+            .map(|(conn_key, _)| *conn_key);
+        if let Some(conn_key) = oldest {
+            self.close(conn_key, CloseReason::Evicted, events);
+        }
+    }
+
+    // 关闭连接，还在缓存中的数据，跳过缺失的部分后交付
+    fn close(
+        &mut self,
+        conn_key: (SocketAddr, SocketAddr),
+        reason: CloseReason,
+        events: &mut Vec<StreamEvent>,
+    ) {
+        let Some(mut connection) = self.connections.remove(&conn_key) else {
+            return;
+        };
+        let key = connection.key;
+        for (key, half) in [
+            (key, &mut connection.client),
+            (key.reverse(), &mut connection.server),
+        ] {
+            let mut data = Vec::new();
+            while !half.pending.is_empty() {
+                half.skip_gap(half.next_seq.unwrap_or_default());
+                half.drain(&mut data);
+            }
+            if !data.is_empty() {
+                let application_pro = *connection
+                    .application_pro
+                    .get_or_insert_with(|| analyze::analyze_application_payload(&data));
+                events.push(StreamEvent::Data {
+                    key,
+                    application_pro,
+                    ts: connection.last_seen,
+                    data,
+                });
+            }
+        }
+        events.push(StreamEvent::Close { key, reason });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CLIENT_PORT: u16 = 5000;
+    const SERVER_PORT: u16 = 80;
+    const ACK: u8 = analyze::TCP_ACK;
+
+    // 以太网 + IPv4 + TCP
+    fn packet(src_port: u16, dst_port: u16, seq: u32, flags: u8, payload: &[u8]) -> PacketInfo {
+        let (src_ip, dst_ip) = if src_port == CLIENT_PORT {
+            ([10, 0, 0, 1], [10, 0, 0, 2])
+        } else {
+            ([10, 0, 0, 2], [10, 0, 0, 1])
+        };
+        let mut data = vec![0, 1, 2, 3, 4, 5, 0, 1, 2, 3, 4, 6, 0x08, 0x00];
+        let total_len = (20 + 20 + payload.len()) as u16;
+        data.extend_from_slice(&[0x45, 0, 0, 0, 0, 0, 0, 0, 64, 6, 0, 0]);
+        data[16..18].copy_from_slice(&total_len.to_be_bytes());
+        data.extend_from_slice(&src_ip);
+        data.extend_from_slice(&dst_ip);
+        data.extend_from_slice(&src_port.to_be_bytes());
+        data.extend_from_slice(&dst_port.to_be_bytes());
+        data.extend_from_slice(&seq.to_be_bytes());
+        data.extend_from_slice(&[0, 0, 0, 0, 0x50, flags, 0x10, 0, 0, 0, 0, 0]);
+        data.extend_from_slice(payload);
+        PacketInfo {
+            pro_type: ProType::from_with_linktype(&pcap::Linktype::ETHERNET, &data),
+            ts: Duration::from_secs(1),
+            caplen: data.len() as u32,
+            len: data.len() as u32,
+            data,
+        }
+    }
+
+    // 客户端发送的数据，内容由位置决定，方便检查顺序
+    fn content(start: usize, len: usize) -> Vec<u8> {
+        (start..start + len).map(|pos| (pos % 251) as u8).collect()
+    }
+
+    // 客户端从base开始，发送偏移量offset处的len字节
+    fn client(reassembler: &mut Reassembler, base: u32, offset: usize, len: usize) -> Vec<u8> {
+        let seq = base.wrapping_add(offset as u32);
+        let packet = packet(CLIENT_PORT, SERVER_PORT, seq, ACK, &content(offset, len));
+        data(&reassembler.push(&packet, &packet.pro_type))
+    }
+
+    fn push(reassembler: &mut Reassembler, packet: PacketInfo) -> Vec<StreamEvent> {
+        reassembler.push(&packet, &packet.pro_type)
+    }
+
+    fn data(events: &[StreamEvent]) -> Vec<u8> {
+        events
+            .iter()
+            .filter_map(|event| match event {
+                StreamEvent::Data { data, .. } => Some(data.as_slice()),
+                StreamEvent::Close { .. } => None,
+            })
+            .flatten()
+            .copied()
+            .collect()
+    }
+
+    fn close_reason(events: &[StreamEvent]) -> Option<CloseReason> {
+        events.iter().find_map(|event| match event {
+            StreamEvent::Close { reason, .. } => Some(*reason),
+            StreamEvent::Data { .. } => None,
+        })
+    }
+
+    #[test]
+    fn retransmission() {
+        let mut reassembler = Reassembler::new();
+        assert_eq!(client(&mut reassembler, 1000, 0, 100), content(0, 100));
+        assert!(client(&mut reassembler, 1000, 0, 100).is_empty());
+        assert!(client(&mut reassembler, 1000, 50, 50).is_empty());
+        // 部分重叠，只交付新的部分
+        assert_eq!(client(&mut reassembler, 1000, 50, 100), content(100, 50));
+    }
+
+    #[test]
+    fn overlap_before_first_seq() {
+        // 从连接中途开始抓包，之后收到更早的序列号
+        let mut reassembler = Reassembler::new();
+        let payload = content(100, 100);
+        let segment = packet(CLIENT_PORT, SERVER_PORT, 1000, ACK, &payload);
+        assert_eq!(data(&push(&mut reassembler, segment)), payload);
+        let payload = content(0, 300);
+        let segment = packet(CLIENT_PORT, SERVER_PORT, 900, ACK, &payload);
+        assert_eq!(data(&push(&mut reassembler, segment)), content(200, 100));
+        // 完全在第一个序列号之前
+        let segment = packet(CLIENT_PORT, SERVER_PORT, 800, ACK, &content(0, 50));
+        assert!(data(&push(&mut reassembler, segment)).is_empty());
+    }
+
+    #[test]
+    fn out_of_order_fill() {
+        let mut reassembler = Reassembler::new();
+        let packet = packet(CLIENT_PORT, SERVER_PORT, 999, analyze::TCP_SYN, &[]);
+        assert!(data(&push(&mut reassembler, packet)).is_empty());
+        assert!(client(&mut reassembler, 1000, 20, 10).is_empty());
+        assert!(client(&mut reassembler, 1000, 10, 10).is_empty());
+        assert_eq!(client(&mut reassembler, 1000, 0, 10), content(0, 30));
+        assert_eq!(client(&mut reassembler, 1000, 30, 5), content(30, 5));
+    }
+
+    #[test]
+    fn seq_wraparound() {
+        let mut reassembler = Reassembler::new();
+        let base = u32::MAX - 5;
+        assert_eq!(client(&mut reassembler, base, 0, 4), content(0, 4));
+        // 跨过回绕点乱序到达
+        assert!(client(&mut reassembler, base, 10, 10).is_empty());
+        assert_eq!(client(&mut reassembler, base, 4, 6), content(4, 16));
+        assert!(client(&mut reassembler, base, 2, 10).is_empty());
+    }
+
+    #[test]
+    fn fin_teardown() {
+        let mut reassembler = Reassembler::new();
+        let fin = analyze::TCP_FIN | ACK;
+        let events = push(
+            &mut reassembler,
+            packet(
+                CLIENT_PORT,
+                SERVER_PORT,
+                1000,
+                fin,
+                b"GET / HTTP/1.1\r\n\r\n",
+            ),
+        );
+        assert_eq!(data(&events), b"GET / HTTP/1.1\r\n\r\n");
+        assert_eq!(close_reason(&events), None);
+        let events = push(
+            &mut reassembler,
+            packet(
+                SERVER_PORT,
+                CLIENT_PORT,
+                5000,
+                fin,
+                b"HTTP/1.1 200 OK\r\n\r\n",
+            ),
+        );
+        assert_eq!(close_reason(&events), Some(CloseReason::Fin));
+        match &events[0] {
+            StreamEvent::Data {
+                key,
+                application_pro,
+                ..
+            } => {
+                assert_eq!(key.src.port(), SERVER_PORT);
+                assert_eq!(*application_pro, ApplicationPro::HTTP);
+            }
+            StreamEvent::Close { .. } => panic!("没有数据"),
+        }
+        assert!(reassembler.finish().is_empty());
+    }
+
+    #[test]
+    fn rst_teardown() {
+        let mut reassembler = Reassembler::new();
+        assert_eq!(client(&mut reassembler, 1000, 0, 10), content(0, 10));
+        // 缺失10..20，关闭时跳过
+        assert!(client(&mut reassembler, 1000, 20, 10).is_empty());
+        let rst = packet(SERVER_PORT, CLIENT_PORT, 5000, analyze::TCP_RST, &[]);
+        let events = push(&mut reassembler, rst);
+        assert_eq!(data(&events), content(20, 10));
+        assert_eq!(close_reason(&events), Some(CloseReason::Rst));
+        // 连接已经关闭，新的RST不会创建连接
+        let rst = packet(SERVER_PORT, CLIENT_PORT, 5000, analyze::TCP_RST, &[]);
+        assert!(push(&mut reassembler, rst).is_empty());
+        assert!(reassembler.finish().is_empty());
+    }
+
+    fn close_key(events: &[StreamEvent]) -> Option<StreamKey> {
+        events.iter().find_map(|event| match event {
+            StreamEvent::Close { key, .. } => Some(*key),
+            StreamEvent::Data { .. } => None,
+        })
+    }
+
+    #[test]
+    fn late_syn_keeps_direction() {
+        let mut reassembler = Reassembler::new();
+        let syn = analyze::TCP_SYN;
+        let segment = packet(CLIENT_PORT, SERVER_PORT, 999, syn, &[]);
+        assert!(push(&mut reassembler, segment).is_empty());
+        assert_eq!(client(&mut reassembler, 1000, 0, 100), content(0, 100));
+        // 重传的SYN和反向的SYN
+        for (segment, offset) in [
+            (packet(CLIENT_PORT, SERVER_PORT, 999, syn, &[]), 100),
+            (packet(SERVER_PORT, CLIENT_PORT, 4999, syn, &[]), 110),
+        ] {
+            assert!(push(&mut reassembler, segment).is_empty());
+            // 已经交付的数据重传，不会再次交付
+            assert!(client(&mut reassembler, 1000, 0, offset).is_empty());
+            assert_eq!(
+                client(&mut reassembler, 1000, offset, 10),
+                content(offset, 10)
+            );
+        }
+        let key = close_key(&reassembler.finish()).unwrap();
+        assert_eq!(key.src.port(), CLIENT_PORT);
+    }
+
+    #[test]
+    fn syn_before_data_sets_direction() {
+        // 先抓到服务端没有数据的报文，之后收到客户端的SYN
+        let mut reassembler = Reassembler::new();
+        let segment = packet(SERVER_PORT, CLIENT_PORT, 5000, ACK, &[]);
+        assert!(push(&mut reassembler, segment).is_empty());
+        let segment = packet(CLIENT_PORT, SERVER_PORT, 999, analyze::TCP_SYN, &[]);
+        assert!(push(&mut reassembler, segment).is_empty());
+        assert_eq!(client(&mut reassembler, 1000, 0, 10), content(0, 10));
+        let key = close_key(&reassembler.finish()).unwrap();
+        assert_eq!(key.src.port(), CLIENT_PORT);
+    }
+}